@@ -0,0 +1,205 @@
+use crate::{chunk::{VoxelPosition, VoxelID, VoxelState}, chunk_manager::ChunkManager, fluid::Fluid, voxel_data_manager::VoxelDataManager};
+
+// Leaves with this state never decay (e.g. ones placed by the player)
+pub const LEAVES_PERSISTENT: VoxelState = 1;
+// How far away a log can be for leaves to stay alive
+pub const LEAF_DECAY_DISTANCE: i32 = 4;
+
+const UP: VoxelPosition = glam::ivec3(0, 1, 0);
+const DOWN: VoxelPosition = glam::ivec3(0, -1, 0);
+
+// Gives the default voxels their behaviours, voxels that aren't in the manager are skipped
+pub fn register_default_behaviours(voxel_data_manager: &mut VoxelDataManager) {
+    for (name, fluid) in [("Water", Fluid::WATER), ("Lava", Fluid::LAVA)] {
+        voxel_data_manager.configure(name, |data| {
+            data.fluid = Some(fluid);
+            data.on_scheduled_tick = Some(ChunkManager::update_fluid);
+            data.on_neighbour_update = Some(ChunkManager::schedule_fluid_tick);
+        });
+    }
+    voxel_data_manager.configure("Grass Block", |data| {
+        data.on_random_tick = Some(grass_block_random_tick);
+        data.on_neighbour_update = Some(grass_block_neighbour_update);
+    });
+    voxel_data_manager.configure("Leaves", |data| data.on_random_tick = Some(leaves_random_tick));
+    voxel_data_manager.configure("Grass", |data| data.on_neighbour_update = Some(plant_neighbour_update));
+    voxel_data_manager.configure("Wheat", |data| {
+        data.growth_stages = 8;
+        data.on_random_tick = Some(crop_random_tick);
+        data.on_neighbour_update = Some(plant_neighbour_update);
+    });
+    for (name, blast_resistance) in [
+        ("Grass Block", 0.6), ("Dirt", 0.5), ("Sand", 0.5), ("Leaves", 0.2), ("Glass", 0.3),
+        ("Oak Log", 2.0), ("Oak Planks", 3.0), ("Stone", 6.0), ("Cobblestone", 6.0), ("Bricks", 6.0), ("Deep Stone", 12.0),
+    ] {
+        voxel_data_manager.configure(name, |data| data.blast_resistance = blast_resistance);
+    }
+    voxel_data_manager.configure("C4", |data| {
+        data.blast_resistance = 0.0;
+        data.explosive_power = 4.0;
+    });
+    voxel_data_manager.configure("Sand", |data| {
+        data.gravity = true;
+        // A bit loose underfoot
+        data.friction = 0.6;
+    });
+    // Anything with gravity falls, unless it's been given its own handlers
+    for voxel_id in 0..voxel_data_manager.len() {
+        let data = voxel_data_manager.get_voxel_data_mut(voxel_id as VoxelID);
+        if !data.gravity { continue; }
+        data.on_scheduled_tick.get_or_insert(ChunkManager::start_falling);
+        data.on_neighbour_update.get_or_insert(ChunkManager::schedule_fall_check);
+    }
+}
+
+impl ChunkManager {
+    // Sets a voxel the player placed, giving it the state it needs to behave like a placed block (e.g. leaves that don't decay)
+    pub fn place_voxel(&mut self, global_coord: VoxelPosition, voxel_id: VoxelID) -> bool {
+        let state = if Some(voxel_id) == self.voxel_data_manager.get_id("Leaves") { LEAVES_PERSISTENT } else { 0 };
+        self.set_voxel_with_state(global_coord, voxel_id, state)
+    }
+}
+
+// Whether there's an opaque cube on top of a voxel
+fn is_covered(chunk_manager: &ChunkManager, pos: VoxelPosition) -> bool {
+    chunk_manager.get_voxel(pos + UP).is_some_and(|v| chunk_manager.voxel_data_manager.is_opaque_cube(v))
+}
+
+// Grass blocks turn to dirt when they're covered up, otherwise they spread onto dirt nearby
+pub fn grass_block_random_tick(chunk_manager: &mut ChunkManager, pos: VoxelPosition) {
+    let vdm = &chunk_manager.voxel_data_manager;
+    let (Some(grass_block), Some(dirt)) = (vdm.get_id("Grass Block"), vdm.get_id("Dirt")) else { return; };
+    if is_covered(chunk_manager, pos) {
+        chunk_manager.set_voxel(pos, dirt);
+        return;
+    }
+    for _ in 0..4 {
+        let rng = &mut chunk_manager.tick_rng;
+        let target = pos + glam::ivec3(rng.range(-1, 2), rng.range(-3, 2), rng.range(-1, 2));
+        if chunk_manager.get_voxel(target) == Some(dirt) && !is_covered(chunk_manager, target) {
+            chunk_manager.set_voxel(target, grass_block);
+        }
+    }
+}
+
+// Grass blocks turn to dirt straight away when something solid is put on them
+pub fn grass_block_neighbour_update(chunk_manager: &mut ChunkManager, pos: VoxelPosition, _from: VoxelPosition) {
+    if !is_covered(chunk_manager, pos) { return; }
+    if let Some(dirt) = chunk_manager.voxel_data_manager.get_id("Dirt") {
+        chunk_manager.set_voxel(pos, dirt);
+    }
+}
+
+// Whether plants can grow on top of a voxel
+fn is_soil(chunk_manager: &ChunkManager, pos: VoxelPosition) -> bool {
+    let vdm = &chunk_manager.voxel_data_manager;
+    let voxel = chunk_manager.get_voxel(pos);
+    voxel.is_some() && (voxel == vdm.get_id("Dirt") || voxel == vdm.get_id("Grass Block"))
+}
+
+// Plants break when the voxel under them isn't soil anymore
+pub fn plant_neighbour_update(chunk_manager: &mut ChunkManager, pos: VoxelPosition, _from: VoxelPosition) {
+    // Don't break plants at the bottom of the loaded world, there's just nothing loaded under them
+    if chunk_manager.get_voxel(pos + DOWN).is_none() || is_soil(chunk_manager, pos + DOWN) { return; }
+    chunk_manager.set_voxel(pos, 0);
+}
+
+// Leaves disappear if there's no log close enough to hold them up
+pub fn leaves_random_tick(chunk_manager: &mut ChunkManager, pos: VoxelPosition) {
+    if chunk_manager.get_voxel_state(pos) == Some(LEAVES_PERSISTENT) { return; }
+    let Some(log) = chunk_manager.voxel_data_manager.get_id("Oak Log") else { return; };
+    for x in -LEAF_DECAY_DISTANCE..=LEAF_DECAY_DISTANCE {
+        for y in -LEAF_DECAY_DISTANCE..=LEAF_DECAY_DISTANCE {
+            for z in -LEAF_DECAY_DISTANCE..=LEAF_DECAY_DISTANCE {
+                if chunk_manager.get_voxel(pos + glam::ivec3(x, y, z)) == Some(log) {
+                    return;
+                }
+            }
+        }
+    }
+    chunk_manager.set_voxel(pos, 0);
+}
+
+// Crops go up a growth stage (stored in their state) as long as they're planted in dirt or grass
+pub fn crop_random_tick(chunk_manager: &mut ChunkManager, pos: VoxelPosition) {
+    let Some(voxel_id) = chunk_manager.get_voxel(pos) else { return; };
+    let growth_stages = chunk_manager.voxel_data_manager.get_voxel_data(voxel_id).growth_stages;
+    let stage = chunk_manager.get_voxel_state(pos).unwrap_or(0);
+    if stage + 1 >= growth_stages || !is_soil(chunk_manager, pos + DOWN) { return; }
+    chunk_manager.set_voxel_with_state(pos, voxel_id, stage + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::{Chunk, DEFAULT_VOXELS};
+
+    // One chunk of dirt below y = 16 with grass on top, with random ticks turned off
+    fn test_world(seed: u64) -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        register_default_behaviours(&mut voxel_data_manager);
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, seed);
+        chunk_manager.random_tick_speed = 0;
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (dirt, grass_block) = (ids("Dirt"), ids("Grass Block"));
+        let mut voxels = DEFAULT_VOXELS;
+        for (index, voxel) in voxels.iter_mut().enumerate() {
+            let y = Chunk::index_to_coordinates(index).y;
+            if y < 16 {
+                *voxel = dirt;
+            } else if y == 16 {
+                *voxel = grass_block;
+            }
+        }
+        chunk_manager.set_chunk(VoxelPosition::ZERO, voxels);
+        chunk_manager
+    }
+
+    #[test]
+    fn placed_leaves_dont_decay() {
+        let mut chunk_manager = test_world(0);
+        let leaves = chunk_manager.voxel_data_manager.get_id("Leaves").unwrap();
+        let (placed, natural) = (glam::ivec3(4, 20, 4), glam::ivec3(20, 20, 20));
+        chunk_manager.place_voxel(placed, leaves);
+        chunk_manager.set_voxel(natural, leaves);
+        assert_eq!(chunk_manager.get_voxel_state(placed), Some(LEAVES_PERSISTENT));
+
+        leaves_random_tick(&mut chunk_manager, placed);
+        leaves_random_tick(&mut chunk_manager, natural);
+        assert_eq!(chunk_manager.get_voxel(placed), Some(leaves));
+        assert_eq!(chunk_manager.get_voxel(natural), Some(0));
+    }
+
+    #[test]
+    fn placing_other_voxels_has_no_state() {
+        let mut chunk_manager = test_world(0);
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        chunk_manager.place_voxel(glam::ivec3(4, 20, 4), stone);
+        assert_eq!(chunk_manager.get_voxel_state(glam::ivec3(4, 20, 4)), Some(0));
+    }
+
+    #[test]
+    fn ticks_are_deterministic() {
+        // Grass spreading (random ticks) and water flowing (scheduled ticks) should end up the same with the same seed
+        let run = || {
+            let mut chunk_manager = test_world(42);
+            chunk_manager.random_tick_speed = 200;
+            let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+            let (dirt, water) = (ids("Dirt"), ids("Water"));
+            for x in 0..32 {
+                chunk_manager.set_voxel(glam::ivec3(x, 16, 10), dirt);
+            }
+            chunk_manager.set_voxel(glam::ivec3(8, 17, 8), water);
+            chunk_manager.set_voxel(glam::ivec3(24, 17, 24), water);
+            for _ in 0..100 {
+                chunk_manager.tick();
+            }
+            let chunk = chunk_manager.get_chunk(VoxelPosition::ZERO).unwrap();
+            (0..32 * 32 * 32).map(|i| {
+                let local = Chunk::index_to_coordinates(i);
+                (chunk.get_voxel_from_index(i), chunk.get_state_from_coordinate(local))
+            }).collect::<Vec<_>>()
+        };
+        assert!(run() == run());
+    }
+}
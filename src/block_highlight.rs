@@ -0,0 +1,66 @@
+use glam::Vec3;
+
+use crate::{chunk::{VoxelPosition, VoxelID}, chunk_mesh::{ChunkMesh, ChunkMeshBuilder, FACES_AND_OFFSETS, CROSS_1, CROSS_2},
+    voxel_data_manager::{VoxelDataManager, VOXEL_TYPE_CROSS}, physics::{Aabb, CollisionShape}};
+
+// How far the outline sticks out past the block, so it doesn't flicker against the block's own faces
+pub const OUTLINE_INFLATE: f32 = 0.002;
+// How see-through the preview of the block about to be placed is
+pub const GHOST_OPACITY: f32 = 0.4;
+
+// The box a voxel's outlined with, from 0 to 1 on each axis. Partial blocks (e.g. slabs) get their own shape
+pub fn selection_box(voxel_data_manager: &VoxelDataManager, voxel_id: VoxelID) -> Aabb {
+    match voxel_data_manager.get_voxel_data(voxel_id).collision_shape {
+        CollisionShape::Partial(shape) => shape,
+        _ => Aabb::new(Vec3::ZERO, Vec3::ONE),
+    }
+}
+
+// Adds the six faces of a box, texture_id gets the face's plane (the same numbers as in FACES_AND_OFFSETS)
+fn add_box_faces(mesh: &mut ChunkMesh, aabb: Aabb, texture_id: impl Fn(usize) -> u32) {
+    let size = aabb.size();
+    for (face, _, plane) in FACES_AND_OFFSETS {
+        let mut positions = [[0.0; 3]; 4];
+        for (i, vertex_position) in positions.iter_mut().enumerate() {
+            let corner = glam::vec3(face.vertices[i*3] as f32, face.vertices[i*3 + 1] as f32, face.vertices[i*3 + 2] as f32);
+            *vertex_position = (aabb.min + corner * size).to_array();
+        }
+        mesh.add_quad(positions, face.light_level, texture_id(plane as usize), [3.0; 4]);
+    }
+}
+
+// The outline drawn around the voxel being looked at, in world space
+// texture_id should be a texture that's see-through apart from its border (res/outline.png), so drawing it over
+// the block's faces only leaves the edges. Cross shaped voxels get both of their planes outlined instead of a box
+pub fn build_outline_mesh(voxel: VoxelPosition, voxel_id: VoxelID, voxel_data_manager: &VoxelDataManager, texture_id: u32) -> ChunkMesh {
+    let mut mesh = ChunkMesh::new();
+    if voxel_data_manager.get_voxel_type(voxel_id) == VOXEL_TYPE_CROSS {
+        for face in [CROSS_1, CROSS_2] {
+            let mut positions = [[0.0; 3]; 4];
+            for (i, vertex_position) in positions.iter_mut().enumerate() {
+                *vertex_position = (voxel + glam::ivec3(face.vertices[i*3] as i32, face.vertices[i*3 + 1] as i32, face.vertices[i*3 + 2] as i32)).as_vec3().to_array();
+            }
+            mesh.add_quad(positions, 5, texture_id, [3.0; 4]);
+        }
+        return mesh;
+    }
+    let aabb = selection_box(voxel_data_manager, voxel_id).offset(voxel.as_vec3());
+    add_box_faces(&mut mesh, Aabb::new(aabb.min - OUTLINE_INFLATE, aabb.max + OUTLINE_INFLATE), |_| texture_id);
+    mesh
+}
+
+// A copy of the block that would be placed at position, in world space, drawn see-through as a preview
+// Air has nothing to show so it comes out empty
+pub fn build_ghost_mesh(position: VoxelPosition, voxel_id: VoxelID, voxel_data_manager: &VoxelDataManager) -> ChunkMesh {
+    let mut mesh = ChunkMesh::new();
+    if voxel_id == 0 {
+        return mesh;
+    }
+    if voxel_data_manager.get_voxel_type(voxel_id) == VOXEL_TYPE_CROSS {
+        ChunkMeshBuilder::add_cross_faces(&mut mesh, position, voxel_data_manager.get_texture_id(voxel_id, 0), 1.0);
+        return mesh;
+    }
+    let aabb = selection_box(voxel_data_manager, voxel_id).offset(position.as_vec3());
+    add_box_faces(&mut mesh, aabb, |plane| voxel_data_manager.get_texture_id(voxel_id, plane));
+    mesh
+}
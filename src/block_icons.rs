@@ -0,0 +1,233 @@
+use std::{hash::{Hash, Hasher}, collections::hash_map::DefaultHasher};
+
+use glam::Vec2;
+use image::{Rgba, RgbaImage};
+
+use crate::{chunk::VoxelID, chunk_mesh::{TOP_FACE, FRONT_FACE, RIGHT_FACE}, voxel_data_manager::{VoxelDataManager, VOXEL_TYPE_CROSS},
+    textures::{srgb_to_linear, linear_to_srgb}};
+
+// How big each icon is in pixels, twice a 16x16 texture so a texel is drawn about the same size on every face
+pub const ICON_SIZE: u32 = 32;
+// How many icons go in each row of the atlas
+pub const ATLAS_COLUMNS: u32 = 16;
+// Bump this whenever the way icons are drawn changes, so old cached atlases get thrown away
+const ICON_VERSION: u32 = 1;
+
+// The same brightness the shader gives a face (light_level / 5), done on the linear colour like the shader's is
+fn shade(colour: Rgba<u8>, light_level: u8) -> Rgba<u8> {
+    let brightness = light_level as f32 / 5.0;
+    let [r, g, b, a] = colour.0;
+    Rgba([linear_to_srgb(srgb_to_linear(r) * brightness), linear_to_srgb(srgb_to_linear(g) * brightness), linear_to_srgb(srgb_to_linear(b) * brightness), a])
+}
+
+// The texel at uv (0 to 1 from the top left), nearest neighbour so the icons stay as blocky as the world
+fn sample(texture: &RgbaImage, uv: Vec2) -> Rgba<u8> {
+    let x = ((uv.x * texture.width() as f32) as u32).min(texture.width() - 1);
+    let y = ((uv.y * texture.height() as f32) as u32).min(texture.height() - 1);
+    *texture.get_pixel(x, y)
+}
+
+// Where a point is on a parallelogram with corner origin and edges along u and v, None if it's outside it
+fn parallelogram_uv(point: Vec2, origin: Vec2, u: Vec2, v: Vec2) -> Option<Vec2> {
+    let d = point - origin;
+    let determinant = u.perp_dot(v);
+    let uv = glam::vec2(d.perp_dot(v), u.perp_dot(d)) / determinant;
+    (uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()).then_some(uv)
+}
+
+// A cube seen from above its front right corner, so its top, front (on the left) and right sides show
+// The top's a diamond half as tall as it's wide and the sides go straight down, filling the whole icon
+fn draw_isometric(icon: &mut RgbaImage, voxel_data_manager: &VoxelDataManager, voxel_id: VoxelID) {
+    let size = icon.width() as f32;
+    let left = glam::vec2(0.0, size / 4.0);
+    let middle = glam::vec2(size / 2.0, size / 2.0);
+    let down = glam::vec2(0.0, size / 2.0);
+    // Texture sides are left top front right bottom back, along with the face each one's shaded like
+    let faces = [
+        (left, glam::vec2(size / 2.0, size / 4.0), glam::vec2(size / 2.0, -size / 4.0), 1, TOP_FACE.light_level),
+        (left, glam::vec2(size / 2.0, size / 4.0), down, 2, FRONT_FACE.light_level),
+        (middle, glam::vec2(size / 2.0, -size / 4.0), down, 3, RIGHT_FACE.light_level),
+    ];
+    for (x, y, pixel) in icon.enumerate_pixels_mut() {
+        let point = glam::vec2(x as f32 + 0.5, y as f32 + 0.5);
+        for (origin, u, v, side, light_level) in faces {
+            let Some(uv) = parallelogram_uv(point, origin, u, v) else { continue; };
+            let texture = voxel_data_manager.get_texture(voxel_data_manager.get_texture_id(voxel_id, side));
+            // The top's v runs from the front edge to the back one, so flip it to put the back at the top of the texture
+            let uv = if side == 1 { glam::vec2(uv.x, 1.0 - uv.y) } else { uv };
+            let colour = sample(texture, uv);
+            // Gaps in a face (e.g. leaves) show the faces behind, which aren't drawn, so they're left empty
+            if colour.0[3] > 0 {
+                *pixel = shade(colour, light_level);
+            }
+            break;
+        }
+    }
+}
+
+// Cross shaped voxels (e.g. plants) would just be a thin line from the side, so they get their texture laid flat
+// They're not shaded, since they'd be much darker than the blocks next to them otherwise
+fn draw_sprite(icon: &mut RgbaImage, voxel_data_manager: &VoxelDataManager, voxel_id: VoxelID) {
+    let texture = voxel_data_manager.get_texture(voxel_data_manager.get_texture_id(voxel_id, 0));
+    let size = icon.width() as f32;
+    for (x, y, pixel) in icon.enumerate_pixels_mut() {
+        *pixel = sample(texture, glam::vec2(x as f32 + 0.5, y as f32 + 0.5) / size);
+    }
+}
+
+// Draws one voxel's icon, air comes out empty
+pub fn render_icon(voxel_data_manager: &VoxelDataManager, voxel_id: VoxelID, icon_size: u32) -> RgbaImage {
+    let mut icon = RgbaImage::new(icon_size, icon_size);
+    if voxel_id == 0 {
+        return icon;
+    }
+    if voxel_data_manager.get_voxel_type(voxel_id) == VOXEL_TYPE_CROSS {
+        draw_sprite(&mut icon, voxel_data_manager, voxel_id);
+    } else {
+        draw_isometric(&mut icon, voxel_data_manager, voxel_id);
+    }
+    icon
+}
+
+// Every voxel's icon in one image, in rows of ATLAS_COLUMNS in ID order
+pub struct IconAtlas {
+    pub image: RgbaImage,
+    pub icon_size: u32,
+    pub icon_count: usize,
+}
+
+impl IconAtlas {
+    pub fn build(voxel_data_manager: &VoxelDataManager, icon_size: u32) -> Self {
+        let icon_count = voxel_data_manager.len();
+        let mut image = RgbaImage::new(icon_size * ATLAS_COLUMNS, icon_size * IconAtlas::row_count(icon_count));
+        for voxel_id in 0..icon_count {
+            let icon = render_icon(voxel_data_manager, voxel_id as VoxelID, icon_size);
+            let (x, y) = IconAtlas::icon_position(voxel_id as VoxelID, icon_size);
+            image::imageops::replace(&mut image, &icon, x as i64, y as i64);
+        }
+        Self { image, icon_size, icon_count }
+    }
+
+    fn row_count(icon_count: usize) -> u32 {
+        (icon_count as u32).div_ceil(ATLAS_COLUMNS).max(1)
+    }
+    // The pixel position of a voxel's icon in the atlas
+    fn icon_position(voxel_id: VoxelID, icon_size: u32) -> (u32, u32) {
+        let index = voxel_id as u32;
+        ((index % ATLAS_COLUMNS) * icon_size, (index / ATLAS_COLUMNS) * icon_size)
+    }
+    // A copy of one voxel's icon
+    pub fn get_icon(&self, voxel_id: VoxelID) -> RgbaImage {
+        let (x, y) = IconAtlas::icon_position(voxel_id, self.icon_size);
+        image::imageops::crop_imm(&self.image, x, y, self.icon_size, self.icon_size).to_image()
+    }
+    // Where a voxel's icon is in the atlas, from the top left corner of it to the bottom right
+    pub fn tex_coords(&self, voxel_id: VoxelID) -> (Vec2, Vec2) {
+        let (x, y) = IconAtlas::icon_position(voxel_id, self.icon_size);
+        let atlas_size = glam::vec2(self.image.width() as f32, self.image.height() as f32);
+        let min = glam::vec2(x as f32, y as f32);
+        (min / atlas_size, (min + self.icon_size as f32) / atlas_size)
+    }
+
+    // Changes whenever anything an icon's drawn from does (voxels, their textures, or the icon size)
+    pub fn cache_key(voxel_data_manager: &VoxelDataManager, icon_size: u32) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (ICON_VERSION, icon_size, voxel_data_manager.len()).hash(&mut hasher);
+        for voxel_id in 0..voxel_data_manager.len() as VoxelID {
+            voxel_data_manager.get_voxel_type(voxel_id).hash(&mut hasher);
+            (0..6).map(|side| voxel_data_manager.get_texture_id(voxel_id, side)).for_each(|id| id.hash(&mut hasher));
+        }
+        for texture_id in 0..voxel_data_manager.texture_count() as u32 {
+            let texture = voxel_data_manager.get_texture(texture_id);
+            (texture.dimensions(), texture.as_raw()).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    // Loads the atlas from a PNG in directory, e.g. block_icons_32.png, with its cache key next to it in
+    // block_icons_32.key. If either's missing or out of date the atlas is built again and saved over them
+    pub fn load_or_build(directory: &str, voxel_data_manager: &VoxelDataManager, icon_size: u32) -> Self {
+        let image_path = format!("{}/block_icons_{}.png", directory, icon_size);
+        let key_path = format!("{}/block_icons_{}.key", directory, icon_size);
+        let key = format!("{:016x}", IconAtlas::cache_key(voxel_data_manager, icon_size));
+        let icon_count = voxel_data_manager.len();
+        let expected_size = (icon_size * ATLAS_COLUMNS, icon_size * IconAtlas::row_count(icon_count));
+
+        if std::fs::read_to_string(&key_path).is_ok_and(|cached| cached.trim() == key) {
+            if let Ok(image) = image::open(&image_path).map(|i| i.to_rgba8()) {
+                if image.dimensions() == expected_size {
+                    return Self { image, icon_size, icon_count };
+                }
+            }
+        }
+        let atlas = IconAtlas::build(voxel_data_manager, icon_size);
+        let saved = std::fs::create_dir_all(directory)
+            .and_then(|_| atlas.image.save(&image_path).map_err(std::io::Error::other))
+            .and_then(|_| std::fs::write(&key_path, &key));
+        if let Err(e) = saved {
+            println!("Couldn't cache the block icons in {}!! {}", directory, e);
+        }
+        atlas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallelogram_uv_finds_points_on_skewed_faces() {
+        let (origin, u, v) = (glam::vec2(0.0, 8.0), glam::vec2(16.0, 8.0), glam::vec2(0.0, 16.0));
+        assert_eq!(parallelogram_uv(origin, origin, u, v), Some(Vec2::ZERO));
+        assert_eq!(parallelogram_uv(origin + u + v, origin, u, v), Some(Vec2::ONE));
+        assert_eq!(parallelogram_uv(origin + u * 0.25 + v * 0.5, origin, u, v), Some(glam::vec2(0.25, 0.5)));
+        // Inside the bounding box but off the skewed edge
+        assert_eq!(parallelogram_uv(glam::vec2(15.0, 9.0), origin, u, v), None);
+        assert_eq!(parallelogram_uv(origin - v * 0.1, origin, u, v), None);
+    }
+
+    #[test]
+    fn air_icons_are_empty() {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        let icon = render_icon(&voxel_data_manager, 0, ICON_SIZE);
+        assert_eq!(icon.dimensions(), (ICON_SIZE, ICON_SIZE));
+        assert!(icon.pixels().all(|p| p.0[3] == 0));
+    }
+
+    #[test]
+    fn cross_blocks_are_drawn_flat() {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        let grass = voxel_data_manager.get_id("Grass").unwrap();
+        let texture = voxel_data_manager.get_texture(voxel_data_manager.get_texture_id(grass, 0));
+        let icon = render_icon(&voxel_data_manager, grass, ICON_SIZE);
+        // Scaled straight up with no shading
+        let scale = ICON_SIZE / texture.width();
+        for (x, y, pixel) in icon.enumerate_pixels() {
+            assert_eq!(pixel, texture.get_pixel(x / scale, y / scale), "at {} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn blocks_are_drawn_as_cubes() {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        let stone = voxel_data_manager.get_id("Stone").unwrap();
+        let icon = render_icon(&voxel_data_manager, stone, ICON_SIZE);
+        let last = ICON_SIZE - 1;
+        // The corners are outside the cube, and the middle is where all three faces meet
+        for (x, y) in [(0, 0), (last, 0), (0, last), (last, last)] {
+            assert_eq!(icon.get_pixel(x, y).0[3], 0);
+        }
+        assert_eq!(icon.get_pixel(ICON_SIZE / 2, ICON_SIZE / 2).0[3], 255);
+    }
+
+    #[test]
+    fn atlas_tex_coords_cover_each_icon() {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        let atlas = IconAtlas::build(&voxel_data_manager, 8);
+        assert_eq!(atlas.image.width(), 8 * ATLAS_COLUMNS);
+        let (min, max) = atlas.tex_coords(1);
+        assert_eq!(min * glam::vec2(atlas.image.width() as f32, atlas.image.height() as f32), glam::vec2(8.0, 0.0));
+        assert_eq!((max - min) * glam::vec2(atlas.image.width() as f32, atlas.image.height() as f32), Vec2::splat(8.0));
+        assert_eq!(atlas.get_icon(3), render_icon(&voxel_data_manager, 3, 8));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{chunk::VoxelPosition, chunk_manager::ChunkManager};
+
+// What gets called when a voxel next to this one changes, with this voxel's global position and the one that changed
+pub type NeighbourUpdateHandler = fn(&mut ChunkManager, VoxelPosition, VoxelPosition);
+
+// Caps how many updates can be waiting, anything past this is dropped so a chain reaction can't grow forever
+pub const MAX_QUEUED_BLOCK_UPDATES: usize = 16384;
+// How many updates get handled each tick, the rest wait for the next one
+pub const BLOCK_UPDATES_PER_TICK: usize = 4096;
+
+pub const NEIGHBOURS: [VoxelPosition; 6] = [
+    glam::ivec3(-1, 0, 0),
+    glam::ivec3( 1, 0, 0),
+    glam::ivec3( 0,-1, 0),
+    glam::ivec3( 0, 1, 0),
+    glam::ivec3( 0, 0,-1),
+    glam::ivec3( 0, 0, 1),
+];
+
+// Block updates let voxels react to the voxels around them changing (e.g. plants breaking when what they're on is removed)
+// Whenever a voxel changes, it and its six neighbours are queued up, and their handlers get called next tick
+impl ChunkManager {
+    // Queues an update for pos because from changed, returns false if it's already queued or the queue is full
+    pub fn queue_block_update(&mut self, pos: VoxelPosition, from: VoxelPosition) -> bool {
+        if self.block_update_queue.len() >= MAX_QUEUED_BLOCK_UPDATES || !self.queued_block_updates.insert(pos) {
+            return false;
+        }
+        self.block_update_queue.push_back((pos, from));
+        true
+    }
+    // Queues updates for a voxel that's changed and everything touching it
+    // The voxel itself is updated too, so things like fluids start flowing as soon as they're placed
+    pub fn notify_neighbours(&mut self, pos: VoxelPosition) {
+        self.queue_block_update(pos, pos);
+        for offset in NEIGHBOURS {
+            self.queue_block_update(pos + offset, pos);
+        }
+    }
+
+    // Handles up to max_updates queued updates, in the order they were queued. Returns how many were handled
+    pub fn process_block_updates(&mut self, max_updates: usize) -> usize {
+        let mut processed = 0;
+        while processed < max_updates {
+            let Some((pos, from)) = self.block_update_queue.pop_front() else { break; };
+            self.queued_block_updates.remove(&pos);
+            processed += 1;
+            let Some(voxel_id) = self.get_voxel(pos) else { continue; };
+            if let Some(handler) = self.voxel_data_manager.get_voxel_data(voxel_id).on_neighbour_update {
+                handler(self, pos, from);
+            }
+        }
+        processed
+    }
+
+    pub fn pending_block_updates(&self) -> usize {
+        self.block_update_queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_behaviours, chunk::{Chunk, DEFAULT_VOXELS}, voxel_data_manager::VoxelDataManager};
+
+    fn test_world() -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        block_behaviours::register_default_behaviours(&mut voxel_data_manager);
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, 0);
+        chunk_manager.random_tick_speed = 0;
+        chunk_manager
+    }
+
+    #[test]
+    fn queue_is_capped() {
+        let mut chunk_manager = test_world();
+        for i in 0..MAX_QUEUED_BLOCK_UPDATES as i32 {
+            assert!(chunk_manager.queue_block_update(glam::ivec3(i, 0, 0), VoxelPosition::ZERO));
+        }
+        assert!(!chunk_manager.queue_block_update(glam::ivec3(-1, 0, 0), VoxelPosition::ZERO));
+        assert_eq!(chunk_manager.pending_block_updates(), MAX_QUEUED_BLOCK_UPDATES);
+
+        // Once some have been handled there's room again
+        assert_eq!(chunk_manager.process_block_updates(10), 10);
+        assert!(chunk_manager.queue_block_update(glam::ivec3(-1, 0, 0), VoxelPosition::ZERO));
+    }
+
+    #[test]
+    fn updates_are_only_queued_once() {
+        let mut chunk_manager = test_world();
+        assert!(chunk_manager.queue_block_update(glam::ivec3(1, 2, 3), VoxelPosition::ZERO));
+        assert!(!chunk_manager.queue_block_update(glam::ivec3(1, 2, 3), glam::ivec3(1, 1, 3)));
+        chunk_manager.notify_neighbours(glam::ivec3(1, 1, 3));
+        // The voxel itself and its six neighbours, one of which was already queued
+        assert_eq!(chunk_manager.pending_block_updates(), 7);
+        assert_eq!(chunk_manager.process_block_updates(usize::MAX), 7);
+        assert_eq!(chunk_manager.pending_block_updates(), 0);
+    }
+
+    #[test]
+    fn plants_break_when_their_soil_is_removed() {
+        let mut chunk_manager = test_world();
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (dirt, grass, stone) = (ids("Dirt"), ids("Grass"), ids("Stone"));
+        let mut voxels = DEFAULT_VOXELS;
+        for (index, voxel) in voxels.iter_mut().enumerate() {
+            let p = Chunk::index_to_coordinates(index);
+            if p.y == 0 {
+                *voxel = dirt;
+            } else if p.y == 1 && p.z == 0 {
+                *voxel = grass;
+            }
+        }
+        chunk_manager.set_chunk(VoxelPosition::ZERO, voxels);
+
+        chunk_manager.set_voxel(glam::ivec3(4, 0, 0), stone);
+        chunk_manager.tick();
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(4, 1, 0)), Some(0));
+        // The ones next to it are still on dirt
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(3, 1, 0)), Some(grass));
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(5, 1, 0)), Some(grass));
+    }
+
+    #[test]
+    fn world_generation_doesnt_queue_updates() {
+        let mut chunk_manager = test_world();
+        for x in -1..=1 {
+            for z in -1..=1 {
+                chunk_manager.add_chunk(glam::ivec3(x, 0, z));
+                chunk_manager.add_chunk(glam::ivec3(x, 1, z));
+            }
+        }
+        assert_eq!(chunk_manager.pending_block_updates(), 0);
+    }
+}
@@ -2,10 +2,15 @@ use std::f32::consts::PI;
 
 use glium::Surface;
 use glium::glutin;
-use glium::glutin::dpi::PhysicalPosition;
-use crate::window_context;
+use glium::glutin::dpi::{PhysicalPosition, PhysicalSize};
+use glium::glutin::event::MouseScrollDelta;
+use crate::{window_context, chunk::VoxelPosition, frustum::Frustum, input_actions::{Action, Actions}};
 use glam;
 
+// Vertical field of view, in radians
+pub const FOV: f32 = 1.2;
+
+#[derive(Clone)]
 pub struct Camera {
     pub position: glam::Vec3,
     pub rotation: glam::Vec3,
@@ -27,9 +32,26 @@ impl Camera {
         let (width, height) = target.get_dimensions();
         let aspect_ratio = width as f32 / height as f32;
 
-        self.perspective_matrix = glam::Mat4::perspective_lh(1.2, aspect_ratio, 0.01, 1024.0);
+        self.perspective_matrix = glam::Mat4::perspective_lh(FOV, aspect_ratio, 0.01, 1024.0);
         self.perspective_matrix
     }
+    // Everything the camera can see, from the last calculated perspective and view matrices
+    pub fn get_frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.perspective_matrix * self.view_matrix)
+    }
+    // The direction the camera's looking in
+    pub fn forward(&self) -> glam::Vec3 {
+        let (sin_x, cos_x) = self.rotation.x.sin_cos();
+        let (sin_y, cos_y) = self.rotation.y.sin_cos();
+        glam::vec3(-sin_y * cos_x, sin_x, cos_y * cos_x)
+    }
+    pub fn right(&self) -> glam::Vec3 {
+        let (sin_y, cos_y) = self.rotation.y.sin_cos();
+        glam::vec3(cos_y, 0.0, sin_y)
+    }
+    pub fn up(&self) -> glam::Vec3 {
+        self.forward().cross(self.right())
+    }
 }
 
 pub struct FlyCamera {
@@ -42,56 +64,129 @@ impl FlyCamera {
     pub fn new() -> Self {
         Self { speed: 5.0, default_speed: 5.0, fast_speed: 20.0, camera: Camera::new() }
     }
-    pub fn handle_movement(&mut self, kb: &window_context::Keyboard, deltatime: &f32) {
+    pub fn handle_movement(&mut self, actions: &Actions, deltatime: &f32) {
         // Speed
-        if kb.key_pressed(glutin::event::VirtualKeyCode::LControl) {
-            self.speed = self.fast_speed;
-        }
-        if kb.key_released(glutin::event::VirtualKeyCode::LControl) {
-            self.speed = self.default_speed;
-        }
+        self.speed = if actions.action_held(Action::Sprint) { self.fast_speed } else { self.default_speed };
         // Simplified expressions to stop repetition
         let sin_rot = self.camera.rotation.y.sin() * deltatime * self.speed;
         let cos_rot = self.camera.rotation.y.cos() * deltatime * self.speed;
         // Forwards, backwards, and strafing
-        if kb.key_held(glutin::event::VirtualKeyCode::W) {
+        if actions.action_held(Action::MoveForward) {
             self.camera.position.x -= sin_rot;
             self.camera.position.z += cos_rot;
         }
-        if kb.key_held(glutin::event::VirtualKeyCode::S) {
+        if actions.action_held(Action::MoveBackward) {
             self.camera.position.x += sin_rot;
             self.camera.position.z -= cos_rot;
         }
-        if kb.key_held(glutin::event::VirtualKeyCode::A) {
+        if actions.action_held(Action::MoveLeft) {
             self.camera.position.x -= cos_rot;
             self.camera.position.z -= sin_rot;
         }
-        if kb.key_held(glutin::event::VirtualKeyCode::D) {
+        if actions.action_held(Action::MoveRight) {
             self.camera.position.x += cos_rot;
             self.camera.position.z += sin_rot;
         }
         // Up and down
-        if kb.key_held(glutin::event::VirtualKeyCode::Space) {
+        if actions.action_held(Action::Jump) {
             self.camera.position.y += deltatime * self.speed;
         }
-        if kb.key_held(glutin::event::VirtualKeyCode::LShift) {
+        if actions.action_held(Action::Crouch) {
             self.camera.position.y -= deltatime * self.speed;
         }
 
     }
 
     pub fn handle_mouse_looking(&mut self, display: &glium::Display, pos: &PhysicalPosition<f64>) {
-        let cy = pos.x - (display.gl_window().window().inner_size().width/2) as f64;
-        let cx = pos.y - (display.gl_window().window().inner_size().height/2) as f64;
+        self.look(pos, display.gl_window().window().inner_size());
+        FlyCamera::reset_mouse_pos(display);
+    }
+    // Turns the camera by how far the cursor is from the middle of the window
+    // Doesn't touch the window, so recorded input can be played through it without one
+    pub fn look(&mut self, pos: &PhysicalPosition<f64>, window_size: PhysicalSize<u32>) {
+        let cy = pos.x - (window_size.width/2) as f64;
+        let cx = pos.y - (window_size.height/2) as f64;
         self.camera.rotation.y -= cy as f32 / 1000.0;
         self.camera.rotation.x -= cx as f32 / 1000.0;
         self.camera.rotation.x = self.camera.rotation.x.min(PI/2.0).max(-PI/2.0);
         self.camera.rotation.y = (self.camera.rotation.y).rem_euclid(PI*2.0);
-        FlyCamera::reset_mouse_pos(display);
     }
 
     pub fn reset_mouse_pos(display: &glium::Display) {
         let window_size = display.gl_window().window().inner_size();
         let _ = display.gl_window().window().set_cursor_position(glutin::dpi::LogicalPosition::new(window_size.width/2, window_size.height/2));
     }
-}
\ No newline at end of file
+}
+// Turns around a target point, for looking at builds from every side
+// Like the PlayerController it doesn't own a camera, it just moves the one it's given
+pub struct OrbitCamera {
+    pub target: glam::Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    // Radians per pixel dragged
+    pub rotate_speed: f32,
+    // How much the distance is multiplied by per scroll wheel notch
+    pub zoom_speed: f32,
+    // How far the target moves per pixel dragged, scaled by the distance so it feels the same zoomed in or out
+    pub pan_speed: f32,
+    last_mouse_pos: Option<PhysicalPosition<f64>>,
+}
+impl OrbitCamera {
+    pub fn new(target: glam::Vec3) -> Self {
+        Self { target, yaw: 0.0, pitch: -0.5, distance: 32.0, min_distance: 1.0, max_distance: 512.0,
+            rotate_speed: 0.005, zoom_speed: 0.9, pan_speed: 0.0015, last_mouse_pos: None }
+    }
+
+    // Rotates while OrbitRotate is held, pans while OrbitPan is held and zooms with the scroll wheel
+    pub fn handle_mouse(&mut self, m: &window_context::Mouse, actions: &Actions) {
+        let pos = *m.get_pos();
+        let (dx, dy) = match self.last_mouse_pos {
+            Some(last) => ((pos.x - last.x) as f32, (pos.y - last.y) as f32),
+            None => (0.0, 0.0),
+        };
+        self.last_mouse_pos = Some(pos);
+
+        if actions.action_held(Action::OrbitRotate) {
+            self.yaw = (self.yaw - dx * self.rotate_speed).rem_euclid(PI*2.0);
+            self.pitch = (self.pitch - dy * self.rotate_speed).clamp(-PI/2.0 + 0.01, PI/2.0 - 0.01);
+        }
+        if actions.action_held(Action::OrbitPan) {
+            let camera = self.get_camera_orientation();
+            let pan_amount = self.pan_speed * self.distance;
+            self.target += (-camera.right() * dx + camera.up() * dy) * pan_amount;
+        }
+        let scroll = match m.get_scroll_delta() {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            // Roughly how many pixels one notch of a scroll wheel is
+            MouseScrollDelta::PixelDelta(p) => p.y as f32 / 50.0,
+        };
+        if scroll != 0.0 {
+            self.distance = (self.distance * self.zoom_speed.powf(scroll)).clamp(self.min_distance, self.max_distance);
+        }
+    }
+
+    // Points the camera at the middle of a box of voxels (min and max inclusive), far enough back to see all of it
+    pub fn frame_box(&mut self, min: VoxelPosition, max: VoxelPosition) {
+        let (low, high) = (min.min(max).as_vec3(), (min.max(max) + 1).as_vec3());
+        self.target = (low + high) / 2.0;
+        // Fit a sphere around the box into the view, with a bit of space around it
+        let radius = (high - low).length() / 2.0;
+        self.distance = (radius / (FOV / 2.0).sin() * 1.1).clamp(self.min_distance, self.max_distance);
+    }
+
+    // Puts the camera where it should be for the current target, yaw, pitch and distance
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.rotation = glam::vec3(self.pitch, self.yaw, 0.0);
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+
+    // A camera with this one's rotation, for working out directions
+    fn get_camera_orientation(&self) -> Camera {
+        let mut camera = Camera::new();
+        self.apply(&mut camera);
+        camera
+    }
+}
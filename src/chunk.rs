@@ -7,6 +7,9 @@ pub const CHUNK_SIZE_USIZE: usize = CHUNK_SIZE as usize;
 pub const CHUNK_SIZE_MIN1: i32 = CHUNK_SIZE-1;
 
 pub type VoxelID = u8;
+// Extra per-voxel data (e.g. fluid levels), 0 is the default and isn't stored
+pub type VoxelState = u8;
+pub type VoxelCounts = [u32; 256];
 pub type VoxelList = [VoxelID; CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE];
 pub type VoxelPosition = glam::IVec3;
 pub type ChunkPosition = glam::IVec3;
@@ -23,12 +26,24 @@ pub const RELATIVE_NEIGHBOURS: [(usize, i32, ChunkPosition); 6] = [
 // Chunk - 32x32x32 array of voxels
 #[derive(Clone)]
 pub struct Chunk {
-    pub voxels: VoxelList,
+    // Private so every write goes through the set functions and voxel_counts can't go out of date
+    voxels: VoxelList,
     pub position: ChunkPosition,
     pub blocks_to_add: HashMap<VoxelPosition, VoxelID>,
+    // How many of each voxel ID are in the chunk, kept up to date by the set functions
+    voxel_counts: VoxelCounts,
+    // States of voxels that have one, by index
+    pub voxel_states: HashMap<usize, VoxelState>,
+    // Voxels that asked to be ticked, and the tick they want it on
+    pub scheduled_ticks: HashMap<VoxelPosition, u64>,
 }
 
 impl Chunk {
+    pub fn new(position: ChunkPosition, voxels: VoxelList) -> Self {
+        let mut chunk = Chunk { voxels, position, blocks_to_add: HashMap::new(), voxel_counts: [0; 256], voxel_states: HashMap::new(), scheduled_ticks: HashMap::new() };
+        chunk.recount_voxels();
+        chunk
+    }
     // Turns 3D coordinates into an index in a 32x32x32 array
     pub fn coordinates_to_index(coordinate: VoxelPosition) -> usize {
         return ((CHUNK_SIZE * CHUNK_SIZE * coordinate.y) + (CHUNK_SIZE * coordinate.z) + coordinate.x) as usize
@@ -56,15 +71,62 @@ impl Chunk {
     pub fn get_voxel_from_coordinate(&self, coordinate: VoxelPosition) -> VoxelID {
         return self.get_voxel_from_index(Chunk::coordinates_to_index(coordinate))
     }
-    // Sets the voxel at the specified index
+    // Sets the voxel at the specified index, this also resets its state
     pub fn set_voxel_from_index(&mut self, index: usize, voxel_id: VoxelID) {
         assert!(index < CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE, "Tried to check out of bounds block in chunk!! Index: {:?}, Block: {:?}", index, voxel_id);
+        self.voxel_counts[self.voxels[index] as usize] -= 1;
+        self.voxel_counts[voxel_id as usize] += 1;
         self.voxels[index] = voxel_id;
+        self.voxel_states.remove(&index);
     }
     // Sets the voxel at the specified coordinates
     pub fn set_voxel_from_coordinate(&mut self, coordinate: VoxelPosition, voxel_id: VoxelID) {
         self.set_voxel_from_index(Chunk::coordinates_to_index(coordinate), voxel_id);
     }
+    // Every voxel in the chunk, by index
+    pub fn get_voxels(&self) -> &VoxelList {
+        &self.voxels
+    }
+    // Returns the state of the voxel at the specified coordinates
+    pub fn get_state_from_coordinate(&self, coordinate: VoxelPosition) -> VoxelState {
+        *self.voxel_states.get(&Chunk::coordinates_to_index(coordinate)).unwrap_or(&0)
+    }
+    // Sets the state of the voxel at the specified coordinates
+    pub fn set_state_from_coordinate(&mut self, coordinate: VoxelPosition, state: VoxelState) {
+        let index = Chunk::coordinates_to_index(coordinate);
+        if state == 0 {
+            self.voxel_states.remove(&index);
+        } else {
+            self.voxel_states.insert(index, state);
+        }
+    }
+    // Replaces every voxel in the chunk at once, clearing their states
+    pub fn set_voxels(&mut self, voxels: VoxelList) {
+        self.voxels = voxels;
+        self.voxel_states.clear();
+        self.recount_voxels();
+    }
+
+    // Recalculates the cached voxel counts from scratch
+    fn recount_voxels(&mut self) {
+        self.voxel_counts = [0; 256];
+        for &voxel_id in self.voxels.iter() {
+            self.voxel_counts[voxel_id as usize] += 1;
+        }
+    }
+    // Returns how many of a certain voxel are in the chunk
+    pub fn get_voxel_count(&self, voxel_id: VoxelID) -> u32 {
+        self.voxel_counts[voxel_id as usize]
+    }
+    pub fn get_voxel_counts(&self) -> &VoxelCounts {
+        &self.voxel_counts
+    }
+    pub fn air_count(&self) -> u32 {
+        self.get_voxel_count(0)
+    }
+    pub fn solid_count(&self) -> u32 {
+        (CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE) as u32 - self.air_count()
+    }
 }
 
 // For conversions
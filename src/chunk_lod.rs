@@ -0,0 +1,149 @@
+use glam::Vec3;
+
+use crate::{chunk::{ChunkPosition, VoxelPosition, VoxelID, CHUNK_SIZE}, padded_chunk::PaddedChunk, voxel_data_manager::{VoxelDataManager, VOXEL_TYPE_CROSS, VOXEL_TYPE_FLUID},
+    block_updates::NEIGHBOURS};
+
+// LOD 0 is the full chunk, each level after that halves the resolution (so LOD 3 is 4x4x4 cells of 8x8x8 voxels)
+pub const MAX_LOD: u32 = 3;
+
+// How many voxels wide a cell is at a level of detail
+pub fn lod_scale(lod: u32) -> i32 {
+    1 << lod.min(MAX_LOD)
+}
+
+// Decides which level of detail a chunk should use from how far it is from the camera
+pub struct LodPolicy {
+    // The furthest away (in chunks, measured to the middle of the chunk) each level is used, anything past the last uses MAX_LOD
+    pub distances: [f32; MAX_LOD as usize],
+}
+
+impl LodPolicy {
+    pub fn new(distances: [f32; MAX_LOD as usize]) -> Self {
+        Self { distances }
+    }
+    pub fn select_lod(&self, chunk_position: ChunkPosition, camera_position: Vec3) -> u32 {
+        let chunk_center = (chunk_position * CHUNK_SIZE).as_vec3() + (CHUNK_SIZE / 2) as f32;
+        let distance = chunk_center.distance(camera_position) / CHUNK_SIZE as f32;
+        self.distances.iter().position(|&d| distance <= d).unwrap_or(MAX_LOD as usize) as u32
+    }
+    // A bit for each neighbouring chunk (numbered like NEIGHBOURS) that's at a lower level of detail than this one
+    // Chunks have to be meshed again when this changes, see ChunkMeshBuilder::build_lod_chunk_mesh
+    pub fn coarser_neighbours(&self, chunk_position: ChunkPosition, camera_position: Vec3) -> u8 {
+        let lod = self.select_lod(chunk_position, camera_position);
+        NEIGHBOURS.iter().enumerate()
+            .filter(|(_, &offset)| self.select_lod(chunk_position + offset, camera_position) > lod)
+            .fold(0, |bits, (face, _)| bits | 1 << face)
+    }
+}
+
+impl Default for LodPolicy {
+    fn default() -> Self {
+        Self::new([3.0, 5.0, 8.0])
+    }
+}
+
+// A chunk shrunk down for a level of detail, each cell is the most common voxel in it (ignoring air)
+// Cells that are mostly air are air, and so are plants since they'd just turn into big solid blocks
+pub struct LodChunk {
+    pub lod: u32,
+    pub scale: i32,
+    // How many cells across it is
+    pub size: i32,
+    voxels: Vec<VoxelID>,
+}
+
+impl LodChunk {
+    pub fn from_padded_chunk(padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager, lod: u32) -> Self {
+        let scale = lod_scale(lod);
+        let size = CHUNK_SIZE / scale;
+        let mut voxels = vec![0; (size * size * size) as usize];
+        let mut counts = [0u32; 256];
+        for y in 0..size {
+            for z in 0..size {
+                for x in 0..size {
+                    let cell = glam::ivec3(x, y, z);
+                    counts.fill(0);
+                    let mut solid = 0;
+                    for offset_y in 0..scale {
+                        for offset_z in 0..scale {
+                            for offset_x in 0..scale {
+                                let voxel_id = padded.get_voxel(cell * scale + glam::ivec3(offset_x, offset_y, offset_z));
+                                // Plants and fluids are left out, a cell of them would come out as a solid cube
+                                let voxel_type = voxel_data_manager.get_voxel_type(voxel_id);
+                                if voxel_id == 0 || voxel_type == VOXEL_TYPE_CROSS || voxel_type == VOXEL_TYPE_FLUID { continue; }
+                                counts[voxel_id as usize] += 1;
+                                solid += 1;
+                            }
+                        }
+                    }
+                    if solid * 2 < scale * scale * scale { continue; }
+                    // Ties go to the lower ID, so it always comes out the same
+                    let (dominant, _) = counts.iter().enumerate().rev().max_by_key(|&(_, &c)| c).unwrap();
+                    voxels[LodChunk::cell_to_index(size, cell)] = dominant as VoxelID;
+                }
+            }
+        }
+        Self { lod, scale, size, voxels }
+    }
+
+    fn cell_to_index(size: i32, cell: VoxelPosition) -> usize {
+        ((size * size * cell.y) + (size * cell.z) + cell.x) as usize
+    }
+    pub fn cell_out_of_bounds(&self, cell: VoxelPosition) -> bool {
+        cell.cmplt(VoxelPosition::ZERO).any() || cell.cmpge(VoxelPosition::splat(self.size)).any()
+    }
+    // Returns the voxel in a cell, or None if the cell is outside of the chunk
+    pub fn get_voxel(&self, cell: VoxelPosition) -> Option<VoxelID> {
+        if self.cell_out_of_bounds(cell) {
+            return None;
+        }
+        Some(self.voxels[LodChunk::cell_to_index(self.size, cell)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coarser_neighbours() {
+        let policy = LodPolicy::default();
+        let camera = glam::vec3(16.0, 16.0, 16.0);
+        // Everything around the camera's chunk is close enough to be full detail too
+        assert_eq!(policy.coarser_neighbours(glam::ivec3(0, 0, 0), camera), 0);
+        // 3 chunks out along +x is the last full detail chunk, the one past it is LOD 1
+        assert_eq!(policy.select_lod(glam::ivec3(3, 0, 0), camera), 0);
+        assert_eq!(policy.select_lod(glam::ivec3(4, 0, 0), camera), 1);
+        // The chunks beside it are a little further away so they're LOD 1 as well, only the one back towards the camera isn't
+        assert_eq!(policy.coarser_neighbours(glam::ivec3(3, 0, 0), camera), 0b111110);
+        // and nothing's coarser than the furthest level
+        assert_eq!(policy.coarser_neighbours(glam::ivec3(20, 0, 0), camera), 0);
+    }
+
+    #[test]
+    fn dominant_voxel_skips_plants_and_fluids() {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        let ids = |name| voxel_data_manager.get_id(name).unwrap();
+        let (stone, dirt, water, grass) = (ids("Stone"), ids("Dirt"), ids("Water"), ids("Grass"));
+        // Fills the 2x2x2 block of voxels making up cell at LOD 1
+        let fill = |padded: &mut PaddedChunk, cell: VoxelPosition, voxels: [VoxelID; 8]| {
+            for (i, voxel_id) in voxels.into_iter().enumerate() {
+                let i = i as i32;
+                padded.set_voxel(cell * 2 + glam::ivec3(i & 1, (i >> 1) & 1, i >> 2), voxel_id);
+            }
+        };
+        let mut padded = PaddedChunk::new(glam::IVec3::ZERO);
+        // Fewer dirt than water, but the water doesn't count
+        fill(&mut padded, glam::ivec3(0, 0, 0), [dirt, dirt, dirt, stone, water, water, water, water]);
+        // Ties go to the lower ID
+        fill(&mut padded, glam::ivec3(1, 0, 0), [dirt, dirt, stone, stone, grass, grass, 0, 0]);
+        // Not enough solid voxels to fill the cell once the water and plants are left out
+        fill(&mut padded, glam::ivec3(2, 0, 0), [stone, stone, stone, water, water, water, water, water]);
+        fill(&mut padded, glam::ivec3(3, 0, 0), [stone, grass, grass, grass, grass, grass, grass, grass]);
+        fill(&mut padded, glam::ivec3(4, 0, 0), [water; 8]);
+
+        let lod_chunk = LodChunk::from_padded_chunk(&padded, &voxel_data_manager, 1);
+        let cells: Vec<_> = (0..5).map(|x| lod_chunk.get_voxel(glam::ivec3(x, 0, 0))).collect();
+        assert_eq!(cells, [Some(dirt), Some(dirt), Some(0), Some(0), Some(0)]);
+    }
+}
@@ -1,19 +1,41 @@
-use std::{collections::HashMap, cmp::Ordering, hash::Hash, default};
+use std::{collections::{HashMap, HashSet, VecDeque}, cmp::Ordering, hash::Hash, default};
 use {bracket_noise::prelude::*, bracket_random::prelude::RandomNumberGenerator};
 
-use crate::{chunk::{Convert, Chunk, self, ChunkPosition, VoxelPosition, VoxelID, VoxelList}, voxel_data_manager::VoxelDataManager};
+use crate::{chunk::{Convert, Chunk, self, ChunkPosition, VoxelPosition, VoxelID, VoxelList, VoxelState}, voxel_data_manager::VoxelDataManager, explosion::{PrimedExplosive, ExplosionResult}, falling_block::FallingBlock};
 
 pub struct ChunkManager {
     pub chunks: HashMap<ChunkPosition, Chunk>,
     pub chunk_voxel_queue: HashMap<ChunkPosition, HashMap<VoxelPosition, VoxelID>>,
     pub voxel_data_manager: VoxelDataManager,
     noise: FastNoise,
+    // Chunks whose meshes are out of date
+    dirty_chunks: HashSet<ChunkPosition>,
+    pub seed: u64,
+    // How many ticks have happened
+    pub tick_count: u64,
+    // How many random voxels in each chunk get a random tick every tick
+    pub random_tick_speed: u32,
+    // Used for anything random that happens during ticks, seeded so a world always ticks the same way
+    pub tick_rng: RandomNumberGenerator,
+    // Voxels waiting for a block update and the voxel that caused it, along with a set of them so nothing's queued twice
+    pub block_update_queue: VecDeque<(VoxelPosition, VoxelPosition)>,
+    pub queued_block_updates: HashSet<VoxelPosition>,
+    // Explosives that have been lit, and the results of explosions that have gone off during ticks
+    pub primed_explosives: Vec<PrimedExplosive>,
+    pub explosions: Vec<ExplosionResult>,
+    pub falling_blocks: Vec<FallingBlock>,
 }
 
 impl ChunkManager {
     pub fn new(voxel_data_manager: VoxelDataManager) -> Self {
         let mut rng = RandomNumberGenerator::new();
-        let mut cm = ChunkManager { chunks: HashMap::new(), noise: FastNoise::seeded(rng.next_u64()), voxel_data_manager: voxel_data_manager, chunk_voxel_queue: HashMap::new()};
+        ChunkManager::new_with_seed(voxel_data_manager, rng.next_u64())
+    }
+    pub fn new_with_seed(voxel_data_manager: VoxelDataManager, seed: u64) -> Self {
+        let mut cm = ChunkManager { chunks: HashMap::new(), noise: FastNoise::seeded(seed), voxel_data_manager: voxel_data_manager, chunk_voxel_queue: HashMap::new(), dirty_chunks: HashSet::new(),
+            seed, tick_count: 0, random_tick_speed: 3, tick_rng: RandomNumberGenerator::seeded(seed),
+            block_update_queue: VecDeque::new(), queued_block_updates: HashSet::new(),
+            primed_explosives: vec![], explosions: vec![], falling_blocks: vec![] };
         // Set up noise
         cm.noise.set_noise_type(NoiseType::SimplexFractal);
         cm.noise.set_fractal_type(FractalType::Billow);
@@ -25,9 +47,18 @@ impl ChunkManager {
         cm
     }
     pub fn add_chunk(&mut self, position: ChunkPosition) {
-        let c: Chunk = chunk::Chunk{position:position, voxels: self.get_world_generation(position), blocks_to_add: HashMap::new()};
+        let c: Chunk = chunk::Chunk::new(position, self.get_world_generation(position));
     
         self.chunks.insert(position, c);
+        self.mark_chunk_and_neighbours_dirty(position);
+    }
+    // Adds a chunk with the given voxels, or replaces the voxels if the chunk already exists
+    pub fn set_chunk(&mut self, position: ChunkPosition, voxels: VoxelList) {
+        match self.get_chunk_mut(position) {
+            Some(chunk) => chunk.set_voxels(voxels),
+            None => { self.chunks.insert(position, Chunk::new(position, voxels)); }
+        }
+        self.mark_chunk_and_neighbours_dirty(position);
     }
     pub fn get_chunk(&self, position: VoxelPosition) -> Option<&Chunk> {
         self.chunks.get(&position)
@@ -41,19 +72,112 @@ impl ChunkManager {
             _ => {None}
         }
     }
+    pub fn get_voxel_state(&self, global_coord: VoxelPosition) -> Option<VoxelState> {
+        self.get_chunk(Convert::global_to_chunk(global_coord)).map(|chunk| chunk.get_state_from_coordinate(Convert::global_to_local(global_coord)))
+    }
     pub fn set_voxel(&mut self, global_coord: VoxelPosition, voxel_id: VoxelID) -> bool {
+        self.set_voxel_with_state(global_coord, voxel_id, 0)
+    }
+    pub fn set_voxel_with_state(&mut self, global_coord: VoxelPosition, voxel_id: VoxelID, state: VoxelState) -> bool {
         match self.get_chunk_mut(Convert::global_to_chunk(global_coord)) {
             Some(chunk) => {
-                chunk.set_voxel_from_coordinate(Convert::global_to_local(global_coord), voxel_id); true},
+                let local_coord = Convert::global_to_local(global_coord);
+                if chunk.get_voxel_from_coordinate(local_coord) != voxel_id || chunk.get_state_from_coordinate(local_coord) != state {
+                    chunk.set_voxel_from_coordinate(local_coord, voxel_id);
+                    chunk.set_state_from_coordinate(local_coord, state);
+                    self.mark_voxel_dirty(global_coord);
+                    self.notify_neighbours(global_coord);
+                }
+                true},
             _ => {false}
         }
     }
+
+    // Applies a batch of voxel changes at once, looking each chunk up once rather than once per voxel
+    // Voxels in chunks that aren't loaded are skipped. Returns the chunks that were marked as dirty
+    pub fn set_voxels(&mut self, changes: &[(VoxelPosition, VoxelID)]) -> HashSet<ChunkPosition> {
+        let mut by_chunk: HashMap<ChunkPosition, Vec<(VoxelPosition, VoxelID)>> = HashMap::new();
+        for &(global_coord, voxel_id) in changes {
+            by_chunk.entry(Convert::global_to_chunk(global_coord)).or_default().push((global_coord, voxel_id));
+        }
+        let mut changed: Vec<VoxelPosition> = vec![];
+        for (chunk_position, chunk_changes) in by_chunk {
+            let Some(chunk) = self.get_chunk_mut(chunk_position) else { continue; };
+            for (global_coord, voxel_id) in chunk_changes {
+                let local_coord = Convert::global_to_local(global_coord);
+                if chunk.get_voxel_from_coordinate(local_coord) != voxel_id || chunk.get_state_from_coordinate(local_coord) != 0 {
+                    chunk.set_voxel_from_coordinate(local_coord, voxel_id);
+                    changed.push(global_coord);
+                }
+            }
+        }
+        // Keep block updates in the same order no matter what order the HashMap went in
+        changed.sort_by_key(|p| (p.y, p.z, p.x));
+        let mut dirty = HashSet::new();
+        for global_coord in changed {
+            dirty.extend(self.chunks_touching_voxel(global_coord));
+            self.notify_neighbours(global_coord);
+        }
+        self.dirty_chunks.extend(dirty.iter().copied());
+        dirty
+    }
+
+    // The loaded chunk a voxel is in, along with every neighbouring chunk (faces, edges and corners) it's touching
+    // Those are the ones whose mesh or AO depends on it
+    pub fn chunks_touching_voxel(&self, global_coord: VoxelPosition) -> Vec<ChunkPosition> {
+        let chunk_position = Convert::global_to_chunk(global_coord);
+        let local_coord = Convert::global_to_local(global_coord);
+        // Which way (if any) the voxel touches a neighbour on each axis
+        let touching = local_coord.to_array().map(|c| match c {
+            0 => -1,
+            chunk::CHUNK_SIZE_MIN1 => 1,
+            _ => 0,
+        });
+        let mut chunks = vec![];
+        for x in [0, touching[0]] {
+            for y in [0, touching[1]] {
+                for z in [0, touching[2]] {
+                    let p = chunk_position + glam::ivec3(x, y, z);
+                    if self.chunks.contains_key(&p) && !chunks.contains(&p) {
+                        chunks.push(p);
+                    }
+                }
+            }
+        }
+        chunks
+    }
+    // Marks every chunk whose mesh depends on a voxel as dirty
+    pub fn mark_voxel_dirty(&mut self, global_coord: VoxelPosition) {
+        let chunks = self.chunks_touching_voxel(global_coord);
+        self.dirty_chunks.extend(chunks);
+    }
+    // Marks a chunk as dirty along with all 26 of its neighbours, for when the whole chunk changes
+    pub fn mark_chunk_and_neighbours_dirty(&mut self, chunk_position: ChunkPosition) {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let p = chunk_position + glam::ivec3(x, y, z);
+                    if self.chunks.contains_key(&p) {
+                        self.dirty_chunks.insert(p);
+                    }
+                }
+            }
+        }
+    }
+    // Takes every chunk that needs its mesh rebuilding, leaving none marked as dirty
+    pub fn drain_dirty(&mut self) -> HashSet<ChunkPosition> {
+        std::mem::take(&mut self.dirty_chunks)
+    }
     
     pub fn get_world_generation(&mut self, chunk_pos: ChunkPosition) -> VoxelList {
         // TODO: Fix whatever the fuck this shit is (and make it a seperate file)
         // and also make random numbers part of chunk_manager
         // God, why don't i just do things right the first time?! 
-        let mut rng = RandomNumberGenerator::new();
+        // Seeded from the chunk's position so the same seed always makes the same world
+        let mut rng = RandomNumberGenerator::seeded(self.seed
+            ^ (chunk_pos.x as u64).wrapping_mul(0x9E3779B97F4A7C15)
+            ^ (chunk_pos.y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F)
+            ^ (chunk_pos.z as u64).wrapping_mul(0x165667B19E3779F9));
 
         let mut voxels = chunk::DEFAULT_VOXELS;
         let mut voxels_to_add: HashMap<VoxelPosition, VoxelID> = HashMap::new();
@@ -88,8 +212,10 @@ impl ChunkManager {
                                     // outisde chunk
                                     let global_block_pos = glam::ivec3(x, trunkheight+y, z) + (chunk_pos * 32);
                                     let outside_chunk_pos = Convert::global_to_chunk(global_block_pos);
-                                    if self.chunks.contains_key(&outside_chunk_pos) {
-                                        self.set_voxel(global_block_pos, trunktype);
+                                    if let Some(outside_chunk) = self.chunks.get_mut(&outside_chunk_pos) {
+                                        // Written straight into the chunk rather than through set_voxel, add_chunk marks
+                                        // the chunks around the new one dirty anyway
+                                        outside_chunk.set_voxel_from_coordinate(Convert::global_to_local(global_block_pos), trunktype);
                                     } else {
                                         if !self.chunk_voxel_queue.contains_key(&outside_chunk_pos) {
                                             self.chunk_voxel_queue.insert(outside_chunk_pos, HashMap::new());
@@ -2,7 +2,7 @@ use glam;
 use glium::implement_vertex;
 implement_vertex!(ChunkVertex, position, tex_coords, light_level, texture_id, ambient_occlusion);
 
-use crate::{chunk::{Chunk, VoxelPosition, ChunkPosition, Convert, VoxelID}, chunk_manager::ChunkManager, voxel_data_manager::VoxelDataManager};
+use crate::{chunk::{Chunk, VoxelPosition, ChunkPosition, VoxelID, CHUNK_SIZE, CHUNK_SIZE_USIZE}, chunk_manager::ChunkManager, padded_chunk::PaddedChunk, voxel_data_manager::{VoxelDataManager, RenderLayer, VOXEL_TYPE_CUBE, VOXEL_TYPE_FLUID}, fluid::Fluid, falling_block::FallingBlock, chunk_visibility::ChunkVisibility, chunk_lod::LodChunk, block_updates::NEIGHBOURS};
 
 #[derive(Copy, Clone)]
 pub struct ChunkVertex {
@@ -22,17 +22,6 @@ pub struct BlocksAround {
     pub blocks: [u8; 8],
 }
 
-const BLOCKS_AROUND_RELATIVE: [VoxelPosition; 8] = [
-    glam::ivec3(-1, 1, -1),
-    glam::ivec3( 0, 1, -1),
-    glam::ivec3( 1, 1, -1),
-    glam::ivec3(-1, 1,  0),
-    glam::ivec3( 1, 1,  0),
-    glam::ivec3(-1, 1,  1),
-    glam::ivec3( 0, 1,  1),
-    glam::ivec3( 1, 1,  1),
-];
-
 
 pub const FRONT_FACE  : MeshFace = MeshFace{ vertices: [1, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1], light_level: 4 };
 pub const BACK_FACE   : MeshFace = MeshFace{ vertices: [0, 1, 0, 1, 1, 0, 1, 0, 0, 0, 0, 0], light_level: 4 };
@@ -54,27 +43,78 @@ impl ChunkMesh {
         Self { vertices: vec![], indices: vec![], indices_count: 0 }
     }
     pub fn add_face(&mut self, face: MeshFace, position: VoxelPosition, texture_id: u32, ao: [f32;4]) {
+        let mut positions = [[0.0; 3]; 4];
         let mut index: usize = 0;
-        for i in 0..4 {
+        for vertex_position in positions.iter_mut() {
             let x = face.vertices[index] as i32 + position.x;
             index += 1;
             let y = face.vertices[index] as i32 + position.y;
             index += 1;
             let z = face.vertices[index] as i32 + position.z;
             index += 1;
-            self.vertices.push(ChunkVertex { position: [x as f32, y as f32, z as f32], tex_coords: i as u8, light_level: face.light_level, texture_id: texture_id, ambient_occlusion: ao[i] });
+            *vertex_position = [x as f32, y as f32, z as f32];
+        }
+        self.add_quad(positions, face.light_level, texture_id, ao);
+    }
+    // Adds a quad with any vertex positions, for faces that don't line up with the voxel grid (e.g. fluid surfaces)
+    pub fn add_quad(&mut self, positions: [[f32; 3]; 4], light_level: u8, texture_id: u32, ao: [f32;4]) {
+        for i in 0..4 {
+            self.vertices.push(ChunkVertex { position: positions[i], tex_coords: i as u8, light_level, texture_id, ambient_occlusion: ao[i] });
         }
+        // Split the quad along the diagonal with the brightest AO, otherwise the lighting looks different depending on
+        // which way the face is rotated (e.g. one dark corner would bleed across the whole face)
+        let first = if ao[1] + ao[3] > ao[0] + ao[2] { 1 } else { 0 };
         // First triangle
-        self.indices.push(self.indices_count as u32);
-        self.indices.push(self.indices_count as u32 + 1);
-        self.indices.push(self.indices_count as u32 + 2);
+        self.indices.push(self.indices_count as u32 + first);
+        self.indices.push(self.indices_count as u32 + first + 1);
+        self.indices.push(self.indices_count as u32 + first + 2);
         // Second triangle
-        self.indices.push(self.indices_count as u32 + 2);
-        self.indices.push(self.indices_count as u32 + 3);
-        self.indices.push(self.indices_count as u32);
+        self.indices.push(self.indices_count as u32 + first + 2);
+        self.indices.push(self.indices_count as u32 + (first + 3) % 4);
+        self.indices.push(self.indices_count as u32 + first);
         
         self.indices_count += 4;
     }
+
+    // Reorders the faces so the furthest away from view_position (relative to the chunk) are drawn first
+    // Translucent meshes need this to blend properly
+    pub fn sort_back_to_front(&mut self, view_position: glam::Vec3) {
+        let mut faces: Vec<(f32, &[u32])> = self.indices.chunks(6).map(|face_indices| {
+            // Every face has its own 4 vertices, so the lowest index is its first vertex
+            let first = *face_indices.iter().min().unwrap() as usize;
+            let centre = self.vertices[first..first+4].iter()
+                .fold(glam::Vec3::ZERO, |sum, v| sum + glam::Vec3::from(v.position)) / 4.0;
+            (centre.distance_squared(view_position), face_indices)
+        }).collect();
+        faces.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.indices = faces.into_iter().flat_map(|(_, face_indices)| face_indices.iter().copied()).collect();
+    }
+}
+
+// A chunk's meshes, one for each render layer
+pub struct ChunkMeshLayers {
+    pub opaque: ChunkMesh,
+    pub cutout: ChunkMesh,
+    pub translucent: ChunkMesh,
+    // Which of the chunk's faces can see each other, worked out while meshing for cave culling
+    pub visibility: ChunkVisibility,
+}
+impl ChunkMeshLayers {
+    pub fn new() -> Self {
+        Self { opaque: ChunkMesh::new(), cutout: ChunkMesh::new(), translucent: ChunkMesh::new(), visibility: ChunkVisibility::ALL }
+    }
+    pub fn get_mut(&mut self, layer: RenderLayer) -> &mut ChunkMesh {
+        match layer {
+            RenderLayer::Opaque => &mut self.opaque,
+            RenderLayer::Cutout => &mut self.cutout,
+            RenderLayer::Translucent => &mut self.translucent,
+        }
+    }
+}
+impl Default for ChunkMeshLayers {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // A list of block faces and where to look (relatively) to check if they should be added to the mesh or not
@@ -89,105 +129,362 @@ pub const FACES_AND_OFFSETS: [(MeshFace, glam::IVec3, u8); 6] = [
 
 pub struct ChunkMeshBuilder { }
 impl ChunkMeshBuilder {
-    // Builds a chunk mesh from a given chunk
-    pub fn build_chunk_mesh(chunk_position: ChunkPosition, chunk_manager: &mut ChunkManager) -> ChunkMesh {
-        // Make the mesh
-        let mut mesh = ChunkMesh::new();
-
-        let chunk = chunk_manager.get_chunk(chunk_position);
-        if chunk.is_none() {
-            println!("Tried to build mesh of chunk that doesn't exist!! {:?}", chunk_position);
-            return mesh;
+    // Builds a chunk's meshes from a given chunk
+    pub fn build_chunk_mesh(chunk_position: ChunkPosition, chunk_manager: &mut ChunkManager) -> ChunkMeshLayers {
+        // Copy the chunk and its border once, rather than looking up neighbours through the chunk manager for every face
+        match PaddedChunk::from_chunk_manager(chunk_position, chunk_manager) {
+            Some(padded) => ChunkMeshBuilder::build_padded_chunk_mesh(&padded, &chunk_manager.voxel_data_manager, 0),
+            None => {
+                println!("Tried to build mesh of chunk that doesn't exist!! {:?}", chunk_position);
+                ChunkMeshLayers::new()
+            }
         }
-        let chunk = chunk.unwrap();
+    }
+
+    // Builds a chunk's meshes from a padded copy of the chunk
+    // coarser_neighbours has a bit set for each neighbouring chunk (numbered like NEIGHBOURS) drawn at a lower level of detail
+    pub fn build_padded_chunk_mesh(padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager, coarser_neighbours: u8) -> ChunkMeshLayers {
+        // Make the meshes
+        let mut meshes = ChunkMeshLayers::new();
 
         // For every block in the chunk
-        for (i, &voxel_id) in chunk.voxels.iter().enumerate() {
+        for i in 0..CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE*CHUNK_SIZE_USIZE {
+            let coord = Chunk::index_to_coordinates(i);
+            let voxel_id = padded.get_voxel(coord);
             // If it's air
             if voxel_id == 0 { continue; }
+            let mesh = meshes.get_mut(voxel_data_manager.get_render_layer(voxel_id));
             // For each type of block
-            if chunk_manager.voxel_data_manager.get_voxel_type(voxel_id) == 0 {
-                // For every face of the block, if it's neighbour is transparent, add the face to the mesh
-                for (face, offset, plane) in FACES_AND_OFFSETS {
-                    let neighbour_coord = Chunk::index_to_coordinates(i) + offset;
-                    let should_add_face;
-                    if Chunk::coordinate_out_of_bounds(neighbour_coord) {
-                        // If the coordinate is out of bounds, check the neighbouring chunk
-                        let gotten_neighbour = chunk_manager.get_voxel(
-                            Convert::local_to_global(chunk_position,
-                                Chunk::index_to_coordinates(i)) + offset);
-                        should_add_face = gotten_neighbour == None || chunk_manager.voxel_data_manager.get_voxel_type(gotten_neighbour.unwrap_or(0)) == 1;
-                    } else {
-                        // Otherwise check the current chunk
-                        should_add_face = chunk_manager.voxel_data_manager.get_voxel_type(chunk.get_voxel_from_coordinate(neighbour_coord)) == 1;
-                    }
-                    if should_add_face {
-                        let texture_id = chunk_manager.voxel_data_manager.get_texture_id(voxel_id, plane as usize);
-                        // TODO: find a way to optimise this garbage
-                        let ao = ChunkMeshBuilder::get_ambient_occlusion(Convert::local_to_global(chunk_position, Chunk::index_to_coordinates(i) + offset), chunk_manager, plane);
-                        //mesh.add_face(face, Chunk::index_to_coordinates(i), texture_id, [3.0, 3.0, 3.0, 3.0]);
-                        mesh.add_face(face, Chunk::index_to_coordinates(i), texture_id, ao);
+            match voxel_data_manager.get_voxel_type(voxel_id) {
+                VOXEL_TYPE_CUBE => {
+                    // For every face of the block, if it's neighbour can be seen through, add the face to the mesh
+                    for (face, offset, plane) in FACES_AND_OFFSETS {
+                        let faces_coarser_chunk = Chunk::coordinate_out_of_bounds(coord + offset) && ChunkMeshBuilder::is_coarser_neighbour(coarser_neighbours, offset);
+                        if faces_coarser_chunk || ChunkMeshBuilder::should_add_face(voxel_data_manager, voxel_id, padded.get_voxel(coord + offset)) {
+                            let texture_id = voxel_data_manager.get_texture_id(voxel_id, plane as usize);
+                            let ao = ChunkMeshBuilder::get_ambient_occlusion(padded, voxel_data_manager, coord, &face, offset);
+                            mesh.add_face(face, coord, texture_id, ao);
+                        }
                     }
                 }
-            } else {
-                mesh.add_face(CROSS_1, Chunk::index_to_coordinates(i), chunk_manager.voxel_data_manager.get_texture_id(voxel_id, 0), [3.0, 3.0, 3.0, 3.0]);
-                mesh.add_face(CROSS_2, Chunk::index_to_coordinates(i), chunk_manager.voxel_data_manager.get_texture_id(voxel_id, 0), [3.0, 3.0, 3.0, 3.0]);
+                VOXEL_TYPE_FLUID => ChunkMeshBuilder::add_fluid_faces(mesh, padded, voxel_data_manager, coord, voxel_id),
+                _ => {
+                    // Crops get taller as they grow
+                    let growth_stages = voxel_data_manager.get_voxel_data(voxel_id).growth_stages;
+                    let height = if growth_stages == 0 { 1.0 } else { (padded.get_state(coord) + 1) as f32 / growth_stages as f32 };
+                    ChunkMeshBuilder::add_cross_faces(mesh, coord, voxel_data_manager.get_texture_id(voxel_id, 0), height);
+                }
             }
             
         }
 
-        mesh // return mesh 
-    }
-
-    pub fn get_ambient_occlusion(global_voxel_position: VoxelPosition, chunk_manager: &ChunkManager, plane: u8) -> [f32;4] {
-        let x = global_voxel_position.x;
-        let y = global_voxel_position.y;
-        let z = global_voxel_position.z;
-        let (a, b, c, d, e, f, g, h): (f32, f32, f32, f32, f32, f32, f32, f32);
-        if plane % 3 == 1 {
-            a = chunk_manager.is_void(glam::ivec3(x  , y, z-1)) .into();
-            b = chunk_manager.is_void(glam::ivec3(x-1, y, z-1)).into();
-            c = chunk_manager.is_void(glam::ivec3(x-1, y, z)).into();
-            d = chunk_manager.is_void(glam::ivec3(x-1, y, z+1)).into();
-            e = chunk_manager.is_void(glam::ivec3(x  , y, z+1)).into();
-            f = chunk_manager.is_void(glam::ivec3(x+1, y, z+1)).into();
-            g = chunk_manager.is_void(glam::ivec3(x+1, y, z)).into();
-            h = chunk_manager.is_void(glam::ivec3(x+1, y, z-1)).into();
-            if plane == 1 {
-                return [(g + h + a), (a + b + c), (c + d + e), (e + f + g)];
-            } else {
-                return [(a + b + c), (g + h + a), (e + f + g), (c + d + e)];
+        meshes.visibility = ChunkVisibility::from_padded_chunk(padded, voxel_data_manager);
+
+        meshes // return meshes
+    }
+
+    // Builds a chunk's meshes at a level of detail, LOD 0 is the same as build_chunk_mesh
+    // coarser_neighbours has a bit set for each neighbouring chunk (numbered like NEIGHBOURS) drawn at a lower level of detail
+    pub fn build_lod_chunk_mesh(chunk_position: ChunkPosition, chunk_manager: &mut ChunkManager, lod: u32, coarser_neighbours: u8) -> ChunkMeshLayers {
+        match PaddedChunk::from_chunk_manager(chunk_position, chunk_manager) {
+            Some(padded) if lod == 0 => ChunkMeshBuilder::build_padded_chunk_mesh(&padded, &chunk_manager.voxel_data_manager, coarser_neighbours),
+            Some(padded) => ChunkMeshBuilder::build_padded_lod_mesh(&padded, &chunk_manager.voxel_data_manager, lod, coarser_neighbours),
+            None => {
+                println!("Tried to build mesh of chunk that doesn't exist!! {:?}", chunk_position);
+                ChunkMeshLayers::new()
+            }
+        }
+    }
+
+    // Builds a chunk's meshes out of downsampled cells, everything's a cube and there's no AO
+    // Faces on the edge of the chunk are kept unless the neighbouring chunk is solid all the way along them, so they
+    // cover up the gaps between chunks at different levels of detail (where the ground's at a slightly different height)
+    pub fn build_padded_lod_mesh(padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager, lod: u32, coarser_neighbours: u8) -> ChunkMeshLayers {
+        let mut meshes = ChunkMeshLayers::new();
+        let lod_chunk = LodChunk::from_padded_chunk(padded, voxel_data_manager, lod);
+        let scale = lod_chunk.scale;
+        for y in 0..lod_chunk.size {
+            for z in 0..lod_chunk.size {
+                for x in 0..lod_chunk.size {
+                    let cell = glam::ivec3(x, y, z);
+                    let voxel_id = lod_chunk.get_voxel(cell).unwrap_or(0);
+                    if voxel_id == 0 { continue; }
+                    let mesh = meshes.get_mut(voxel_data_manager.get_render_layer(voxel_id));
+                    for (face, offset, plane) in FACES_AND_OFFSETS {
+                        let visible = match lod_chunk.get_voxel(cell + offset) {
+                            Some(neighbour_id) => ChunkMeshBuilder::should_add_face(voxel_data_manager, voxel_id, neighbour_id),
+                            None => ChunkMeshBuilder::is_coarser_neighbour(coarser_neighbours, offset)
+                                || !ChunkMeshBuilder::is_border_solid(padded, voxel_data_manager, cell, scale, offset),
+                        };
+                        if !visible { continue; }
+                        let mut positions = [[0.0; 3]; 4];
+                        for (i, vertex_position) in positions.iter_mut().enumerate() {
+                            let corner = glam::ivec3(face.vertices[i*3] as i32, face.vertices[i*3 + 1] as i32, face.vertices[i*3 + 2] as i32);
+                            *vertex_position = ((cell + corner) * scale).as_vec3().to_array();
+                        }
+                        mesh.add_quad(positions, face.light_level, voxel_data_manager.get_texture_id(voxel_id, plane as usize), [3.0; 4]);
+                    }
+                }
+            }
+        }
+        meshes.visibility = ChunkVisibility::from_padded_chunk(padded, voxel_data_manager);
+        meshes
+    }
+
+    // Whether the chunk next to this one in the direction of offset is drawn at a lower level of detail
+    // Its mesh might be missing the voxels along the border (LOD cells that are mostly air are dropped), so faces
+    // looking into it can't be hidden behind them or there'd be holes between the two chunks
+    fn is_coarser_neighbour(coarser_neighbours: u8, offset: VoxelPosition) -> bool {
+        NEIGHBOURS.iter().position(|&n| n == offset).is_some_and(|face| coarser_neighbours & (1 << face) != 0)
+    }
+
+    // Whether every voxel in the padded border next to one face of a cell is an opaque cube
+    fn is_border_solid(padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager, cell: VoxelPosition, scale: i32, offset: VoxelPosition) -> bool {
+        let axis = if offset.x != 0 { 0 } else if offset.y != 0 { 1 } else { 2 };
+        let border = if offset[axis] < 0 { -1 } else { CHUNK_SIZE };
+        for a in 0..scale {
+            for b in 0..scale {
+                let mut coord = cell * scale;
+                coord[(axis + 1) % 3] += a;
+                coord[(axis + 2) % 3] += b;
+                coord[axis] = border;
+                if !voxel_data_manager.is_opaque_cube(padded.get_voxel(coord)) {
+                    return false;
+                }
             }
-        } else if plane % 3 == 0 {
-            a = chunk_manager.is_void(glam::ivec3(x, y, z-1)).into();
-            b = chunk_manager.is_void(glam::ivec3(x, y-1, z-1)).into();
-            c = chunk_manager.is_void(glam::ivec3(x, y-1, z)).into();
-            d = chunk_manager.is_void(glam::ivec3(x, y-1, z+1)).into();
-            e = chunk_manager.is_void(glam::ivec3(x, y, z+1)).into();
-            f = chunk_manager.is_void(glam::ivec3(x, y+1, z+1)).into();
-            g = chunk_manager.is_void(glam::ivec3(x, y+1, z)).into();
-            h = chunk_manager.is_void(glam::ivec3(x, y+1, z-1)).into(); 
-            let o = [(e + f + g), (g + h + a), (a + b + c), (c + d + e)];
-            if plane == 0 {
-                return o;
-            } else {
-                return [o[1], o[0], o[3], o[2]];
+        }
+        true
+    }
+
+    // Builds one mesh with every falling block in it, positions are in world space rather than relative to a chunk
+    pub fn build_falling_blocks_mesh(falling_blocks: &[FallingBlock], voxel_data_manager: &VoxelDataManager) -> ChunkMesh {
+        let mut mesh = ChunkMesh::new();
+        for block in falling_blocks {
+            for (face, _, plane) in FACES_AND_OFFSETS {
+                let texture_id = voxel_data_manager.get_texture_id(block.voxel_id, plane as usize);
+                let mut positions = [[0.0; 3]; 4];
+                for (i, vertex_position) in positions.iter_mut().enumerate() {
+                    let corner = glam::vec3(face.vertices[i*3] as f32, face.vertices[i*3 + 1] as f32, face.vertices[i*3 + 2] as f32);
+                    *vertex_position = (block.position + corner).to_array();
+                }
+                mesh.add_quad(positions, face.light_level, texture_id, [3.0; 4]);
             }
-        } else {
-            a = chunk_manager.is_void(glam::ivec3(x-1, y, z)).into();
-            b = chunk_manager.is_void(glam::ivec3(x-1, y-1, z)).into();
-            c = chunk_manager.is_void(glam::ivec3(x, y-1, z)).into();
-            d = chunk_manager.is_void(glam::ivec3(x+1, y-1, z)).into();
-            e = chunk_manager.is_void(glam::ivec3(x+1, y, z)).into();
-            f = chunk_manager.is_void(glam::ivec3(x+1, y+1, z)).into();
-            g = chunk_manager.is_void(glam::ivec3(x, y+1, z)).into();
-            h = chunk_manager.is_void(glam::ivec3(x-1, y+1, z)).into(); 
-            let o = [(e + f + g), (g + h + a), (a + b + c), (c + d + e)];
-            if plane == 2 {
-                return o;
-            } else {
-                return [o[1], o[0], o[3], o[2]];
-            } 
-        }
-    }
-}
\ No newline at end of file
+        }
+        mesh
+    }
+
+    // Whether a cube's face should be drawn, given the voxel it's facing
+    // Faces are hidden behind opaque cubes, and between two of the same voxel (so glass next to glass doesn't have faces inside it)
+    pub fn should_add_face(voxel_data_manager: &VoxelDataManager, voxel_id: VoxelID, neighbour_id: VoxelID) -> bool {
+        !voxel_data_manager.is_opaque_cube(neighbour_id) && neighbour_id != voxel_id
+    }
+
+    // Adds the two faces of a cross shaped voxel, squashed down to height
+    pub fn add_cross_faces(mesh: &mut ChunkMesh, coord: VoxelPosition, texture_id: u32, height: f32) {
+        for face in [CROSS_1, CROSS_2] {
+            let mut positions = [[0.0; 3]; 4];
+            for (i, vertex_position) in positions.iter_mut().enumerate() {
+                *vertex_position = [(coord.x + face.vertices[i*3] as i32) as f32, coord.y as f32 + face.vertices[i*3 + 1] as f32 * height, (coord.z + face.vertices[i*3 + 2] as i32) as f32];
+            }
+            mesh.add_quad(positions, face.light_level, texture_id, [3.0; 4]);
+        }
+    }
+
+    // Adds the faces of a fluid, the top is sloped using the heights of the fluid around each corner
+    pub fn add_fluid_faces(mesh: &mut ChunkMesh, padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager, coord: VoxelPosition, voxel_id: VoxelID) {
+        let up = glam::ivec3(0, 1, 0);
+        // The height of the surface at a corner of the voxel's top face
+        let corner_height = |corner_x: i32, corner_z: i32| -> f32 {
+            let (mut total, mut count) = (0.0, 0);
+            // Look at the four voxels that share the corner
+            for x in corner_x-1..=corner_x {
+                for z in corner_z-1..=corner_z {
+                    let c = coord + glam::ivec3(x, 0, z);
+                    let v = padded.get_voxel(c);
+                    if v == voxel_id {
+                        // Fluid with more above it reaches the top
+                        if padded.get_voxel(c + up) == voxel_id {
+                            return 1.0;
+                        }
+                        total += Fluid::height(padded.get_state(c));
+                        count += 1;
+                    } else if !voxel_data_manager.is_opaque_cube(v) {
+                        // Open space pulls the surface down towards it
+                        count += 1;
+                    }
+                }
+            }
+            total / count as f32
+        };
+        let heights = [[corner_height(0, 0), corner_height(0, 1)], [corner_height(1, 0), corner_height(1, 1)]];
+
+        for (face, offset, plane) in FACES_AND_OFFSETS {
+            let neighbour_id = padded.get_voxel(coord + offset);
+            if neighbour_id == voxel_id || voxel_data_manager.is_opaque_cube(neighbour_id) { continue; }
+            let mut positions = [[0.0; 3]; 4];
+            for (i, vertex_position) in positions.iter_mut().enumerate() {
+                let (x, y, z) = (face.vertices[i*3] as usize, face.vertices[i*3 + 1], face.vertices[i*3 + 2] as usize);
+                // Bottom vertices stay on the floor, top ones go to the surface
+                let height = if y == 1 { heights[x][z] } else { 0.0 };
+                *vertex_position = [(coord.x + x as i32) as f32, coord.y as f32 + height, (coord.z + z as i32) as f32];
+            }
+            mesh.add_quad(positions, face.light_level, voxel_data_manager.get_texture_id(voxel_id, plane as usize), [3.0; 4]);
+        }
+    }
+
+    // Works out the AO of each vertex of a face, 3 is fully lit and 0 is fully occluded
+    // The voxels checked are in the layer the face is looking into, so it works the same for every face
+    pub fn get_ambient_occlusion(padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager, coord: VoxelPosition, face: &MeshFace, normal: VoxelPosition) -> [f32;4] {
+        let layer = coord + normal;
+        // Translucent cubes let light through so they don't cast AO
+        let occludes = |p: VoxelPosition| {
+            let v = padded.get_voxel(p);
+            voxel_data_manager.get_voxel_type(v) == 0 && voxel_data_manager.get_render_layer(v) != RenderLayer::Translucent
+        };
+        let mut ao = [0.0; 4];
+        for (i, vertex_ao) in ao.iter_mut().enumerate() {
+            let corner = glam::ivec3(face.vertices[i*3] as i32, face.vertices[i*3 + 1] as i32, face.vertices[i*3 + 2] as i32);
+            // Step towards the vertex on both axes the face lies along (the normal's axis is left at 0)
+            let towards = (corner * 2 - 1) * (1 - normal.abs());
+            let (axis_1, axis_2) = match normal.abs().to_array() {
+                [1, _, _] => (glam::ivec3(0, towards.y, 0), glam::ivec3(0, 0, towards.z)),
+                [_, 1, _] => (glam::ivec3(towards.x, 0, 0), glam::ivec3(0, 0, towards.z)),
+                _         => (glam::ivec3(towards.x, 0, 0), glam::ivec3(0, towards.y, 0)),
+            };
+            *vertex_ao = ChunkMeshBuilder::vertex_ambient_occlusion(occludes(layer + axis_1), occludes(layer + axis_2), occludes(layer + axis_1 + axis_2)) as f32;
+        }
+        ao
+    }
+
+    // The AO of a single vertex from whether the two voxels beside it and the one in the corner are solid
+    // If both sides are solid the corner can't be seen, so it's fully occluded no matter what
+    pub fn vertex_ambient_occlusion(side_1: bool, side_2: bool, corner: bool) -> u8 {
+        if side_1 && side_2 {
+            return 0;
+        }
+        3 - (side_1 as u8 + side_2 as u8 + corner as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::VoxelID;
+
+    // Stone's ID in VoxelDataManager::with_default_voxels
+    const STONE: VoxelID = 3;
+
+    // The AO of the face of a lone voxel at (5, 5, 5), with stone placed at each of solid
+    fn face_ao(face: MeshFace, normal: VoxelPosition, solid: &[VoxelPosition]) -> [f32; 4] {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        let mut padded = PaddedChunk::new(glam::IVec3::ZERO);
+        let coord = glam::ivec3(5, 5, 5);
+        padded.set_voxel(coord, STONE);
+        for &p in solid {
+            padded.set_voxel(p, STONE);
+        }
+        ChunkMeshBuilder::get_ambient_occlusion(&padded, &voxel_data_manager, coord, &face, normal)
+    }
+
+    // The two vertices both triangles of a quad share
+    fn split_diagonal(ao: [f32; 4]) -> [u32; 2] {
+        let mut mesh = ChunkMesh::new();
+        mesh.add_face(TOP_FACE, VoxelPosition::ZERO, 0, ao);
+        let mut shared: Vec<u32> = mesh.indices[0..3].iter().copied().filter(|i| mesh.indices[3..6].contains(i)).collect();
+        shared.sort();
+        [shared[0], shared[1]]
+    }
+
+    #[test]
+    fn top_face_ao() {
+        let up = glam::ivec3(0, 1, 0);
+        // The top face's vertices are at (1, 0), (0, 0), (0, 1) and (1, 1) on x and z
+        assert_eq!(face_ao(TOP_FACE, up, &[]), [3.0; 4]);
+        // One voxel beside the face darkens the two vertices along that edge
+        assert_eq!(face_ao(TOP_FACE, up, &[glam::ivec3(6, 6, 5)]), [2.0, 3.0, 3.0, 2.0]);
+        // Two sides meeting at a vertex fully occlude it
+        assert_eq!(face_ao(TOP_FACE, up, &[glam::ivec3(6, 6, 5), glam::ivec3(5, 6, 4)]), [0.0, 2.0, 3.0, 2.0]);
+        // A corner on its own only touches one vertex
+        assert_eq!(face_ao(TOP_FACE, up, &[glam::ivec3(6, 6, 4)]), [2.0, 3.0, 3.0, 3.0]);
+        // A side and its corner add up
+        assert_eq!(face_ao(TOP_FACE, up, &[glam::ivec3(6, 6, 5), glam::ivec3(6, 6, 4)]), [1.0, 3.0, 3.0, 2.0]);
+        // Voxels that aren't in the layer the face looks into don't count
+        assert_eq!(face_ao(TOP_FACE, up, &[glam::ivec3(6, 5, 5), glam::ivec3(6, 4, 4)]), [3.0; 4]);
+    }
+
+    #[test]
+    fn side_face_ao() {
+        let front = glam::ivec3(0, 0, 1);
+        // The front face's vertices are at (1, 1), (0, 1), (0, 0) and (1, 0) on x and y
+        assert_eq!(face_ao(FRONT_FACE, front, &[]), [3.0; 4]);
+        assert_eq!(face_ao(FRONT_FACE, front, &[glam::ivec3(5, 6, 6)]), [2.0, 2.0, 3.0, 3.0]);
+        assert_eq!(face_ao(FRONT_FACE, front, &[glam::ivec3(5, 6, 6), glam::ivec3(4, 5, 6)]), [2.0, 0.0, 2.0, 3.0]);
+        assert_eq!(face_ao(FRONT_FACE, front, &[glam::ivec3(6, 4, 6)]), [3.0, 3.0, 3.0, 2.0]);
+    }
+
+    // How many faces of a mesh lie flat on the chunk's +x border
+    fn faces_on_positive_x_border(mesh: &ChunkMesh) -> usize {
+        mesh.vertices.chunks(4).filter(|face| face.iter().all(|v| v.position[0] == CHUNK_SIZE as f32)).count()
+    }
+
+    #[test]
+    fn borders_next_to_coarser_chunks_are_kept() {
+        let voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        // Ground 4 voxels deep running on into the chunk at +x
+        let mut padded = PaddedChunk::new(glam::IVec3::ZERO);
+        for y in 0..4 {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..=CHUNK_SIZE {
+                    padded.set_voxel(glam::ivec3(x, y, z), STONE);
+                }
+            }
+        }
+        let positive_x = 1 << 1;
+        // Normally the ground's side is hidden behind the neighbour's voxels
+        let meshes = ChunkMeshBuilder::build_padded_chunk_mesh(&padded, &voxel_data_manager, 0);
+        assert_eq!(faces_on_positive_x_border(&meshes.opaque), 0);
+        // But a coarser neighbour might not draw them, so the side's kept
+        let meshes = ChunkMeshBuilder::build_padded_chunk_mesh(&padded, &voxel_data_manager, positive_x);
+        assert_eq!(faces_on_positive_x_border(&meshes.opaque), 4 * CHUNK_SIZE_USIZE);
+        // Other borders aren't affected
+        let meshes = ChunkMeshBuilder::build_padded_chunk_mesh(&padded, &voxel_data_manager, 1 << 0);
+        assert_eq!(faces_on_positive_x_border(&meshes.opaque), 0);
+
+        // The same goes for LOD meshes, which only draw a face per cell
+        let meshes = ChunkMeshBuilder::build_padded_lod_mesh(&padded, &voxel_data_manager, 1, 0);
+        assert_eq!(faces_on_positive_x_border(&meshes.opaque), 0);
+        let meshes = ChunkMeshBuilder::build_padded_lod_mesh(&padded, &voxel_data_manager, 1, positive_x);
+        assert_eq!(faces_on_positive_x_border(&meshes.opaque), 2 * CHUNK_SIZE_USIZE / 2);
+    }
+
+    #[test]
+    fn vertex_ao_table() {
+        assert_eq!(ChunkMeshBuilder::vertex_ambient_occlusion(false, false, false), 3);
+        assert_eq!(ChunkMeshBuilder::vertex_ambient_occlusion(true, false, false), 2);
+        assert_eq!(ChunkMeshBuilder::vertex_ambient_occlusion(false, false, true), 2);
+        assert_eq!(ChunkMeshBuilder::vertex_ambient_occlusion(true, false, true), 1);
+        assert_eq!(ChunkMeshBuilder::vertex_ambient_occlusion(true, true, false), 0);
+        assert_eq!(ChunkMeshBuilder::vertex_ambient_occlusion(true, true, true), 0);
+    }
+
+    #[test]
+    fn quad_split_keeps_dark_corners_to_one_triangle() {
+        // The quad's split between the two brightest opposite vertices, so a dark vertex is only in one triangle
+        assert_eq!(split_diagonal([0.0, 3.0, 3.0, 3.0]), [1, 3]);
+        assert_eq!(split_diagonal([3.0, 3.0, 0.0, 3.0]), [1, 3]);
+        assert_eq!(split_diagonal([3.0, 0.0, 3.0, 3.0]), [0, 2]);
+        assert_eq!(split_diagonal([3.0, 3.0, 3.0, 0.0]), [0, 2]);
+        // Evenly lit quads keep the default split
+        assert_eq!(split_diagonal([3.0; 4]), [0, 2]);
+        assert_eq!(split_diagonal([2.0, 2.0, 2.0, 2.0]), [0, 2]);
+        // Both triangles still wind the same way as the unflipped quad
+        for ao in [[0.0, 3.0, 3.0, 3.0], [3.0; 4]] {
+            let mut mesh = ChunkMesh::new();
+            mesh.add_face(TOP_FACE, VoxelPosition::ZERO, 0, ao);
+            for triangle in mesh.indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| glam::Vec3::from(mesh.vertices[triangle[i] as usize].position));
+                assert!((b - a).cross(c - a).y > 0.0, "triangle {:?} is wound the wrong way for ao {:?}", triangle, ao);
+            }
+        }
+    }
+}
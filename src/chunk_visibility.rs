@@ -0,0 +1,186 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{chunk::{Chunk, ChunkPosition, CHUNK_SIZE_MIN1, CHUNK_SIZE_USIZE}, padded_chunk::PaddedChunk,
+    voxel_data_manager::VoxelDataManager, block_updates::NEIGHBOURS};
+
+// Faces are numbered the same way as NEIGHBOURS: -x, +x, -y, +y, -z, +z
+// So the face on the other side of face f is always f ^ 1
+pub fn opposite_face(face: usize) -> usize {
+    face ^ 1
+}
+
+// Which faces of a chunk can be seen from which other faces, by looking through the voxels that aren't opaque
+// Stored as a 6x6 matrix of bits, bit (a * 6 + b) is set if a and b are connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkVisibility {
+    connections: u64,
+}
+
+impl ChunkVisibility {
+    // Every face can see every other face, e.g. for an empty chunk
+    pub const ALL: ChunkVisibility = ChunkVisibility { connections: (1 << 36) - 1 };
+    // No face can see any other, e.g. for a completely solid chunk
+    pub const NONE: ChunkVisibility = ChunkVisibility { connections: 0 };
+
+    pub fn connects(&self, a: usize, b: usize) -> bool {
+        self.connections & (1 << (a * 6 + b)) != 0
+    }
+    pub fn connect(&mut self, a: usize, b: usize) {
+        self.connections |= (1 << (a * 6 + b)) | (1 << (b * 6 + a));
+    }
+
+    // Flood fills every open area of the chunk, any faces that the same area touches can see each other
+    pub fn from_padded_chunk(padded: &PaddedChunk, voxel_data_manager: &VoxelDataManager) -> Self {
+        let volume = CHUNK_SIZE_USIZE * CHUNK_SIZE_USIZE * CHUNK_SIZE_USIZE;
+        // Opaque voxels start off visited so the flood fill never goes through them
+        let mut visited: Vec<bool> = (0..volume)
+            .map(|index| voxel_data_manager.is_opaque_cube(padded.get_voxel(Chunk::index_to_coordinates(index))))
+            .collect();
+        // Nothing's opaque, so skip the flood fill
+        if visited.iter().all(|&v| !v) {
+            return ChunkVisibility::ALL;
+        }
+
+        let mut visibility = ChunkVisibility::NONE;
+        let mut stack: Vec<usize> = vec![];
+        for start in 0..volume {
+            if visited[start] { continue; }
+            visited[start] = true;
+            stack.push(start);
+            // Faces touched by this area, as bits
+            let mut faces: u8 = 0;
+            while let Some(index) = stack.pop() {
+                let coord = Chunk::index_to_coordinates(index);
+                for (face, offset) in NEIGHBOURS.iter().enumerate() {
+                    let next = coord + *offset;
+                    if next.cmplt(glam::IVec3::ZERO).any() || next.cmpgt(glam::IVec3::splat(CHUNK_SIZE_MIN1)).any() {
+                        faces |= 1 << face;
+                        continue;
+                    }
+                    let next_index = Chunk::coordinates_to_index(next);
+                    if !visited[next_index] {
+                        visited[next_index] = true;
+                        stack.push(next_index);
+                    }
+                }
+            }
+            for a in 0..6 {
+                for b in 0..6 {
+                    if faces & (1 << a) != 0 && faces & (1 << b) != 0 {
+                        visibility.connect(a, b);
+                    }
+                }
+            }
+        }
+        visibility
+    }
+}
+
+impl Default for ChunkVisibility {
+    fn default() -> Self {
+        ChunkVisibility::ALL
+    }
+}
+
+// Finds every chunk that could be seen from the camera's chunk, going out through chunk faces that are connected
+// It never goes back the way it came (once it's gone +x it can't go -x) so it can't loop round behind walls
+// get_visibility returns None for chunks that aren't loaded, and they aren't gone through
+// If the camera's chunk isn't loaded there's nothing to start from, so None is returned and everything should be drawn
+pub fn find_visible_chunks<'a>(camera_chunk: ChunkPosition, get_visibility: impl Fn(ChunkPosition) -> Option<&'a ChunkVisibility>) -> Option<HashSet<ChunkPosition>> {
+    get_visibility(camera_chunk)?;
+    let mut visible = HashSet::from([camera_chunk]);
+    // Each chunk in the queue has the face it was entered through and the directions that have been gone in to get there
+    let mut queue: VecDeque<(ChunkPosition, Option<usize>, u8)> = VecDeque::from([(camera_chunk, None, 0)]);
+    while let Some((position, entered_from, directions)) = queue.pop_front() {
+        let Some(visibility) = get_visibility(position) else { continue; };
+        for (face, offset) in NEIGHBOURS.iter().enumerate() {
+            // Don't turn back on ourselves
+            if directions & (1 << opposite_face(face)) != 0 { continue; }
+            if entered_from.is_some_and(|entered| !visibility.connects(entered, face)) { continue; }
+            let next = position + *offset;
+            if !visible.insert(next) { continue; }
+            queue.push_back((next, Some(opposite_face(face)), directions | (1 << face)));
+        }
+    }
+    Some(visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::chunk::VoxelID;
+
+    // Stone's ID in VoxelDataManager::with_default_voxels
+    const STONE: VoxelID = 3;
+
+    fn test_voxel_data_manager() -> VoxelDataManager {
+        VoxelDataManager::with_default_voxels(&mut vec![])
+    }
+
+    // A chunk that's stone everywhere apart from where is_air says
+    fn padded_chunk(is_air: impl Fn(glam::IVec3) -> bool) -> PaddedChunk {
+        let mut padded = PaddedChunk::new(glam::IVec3::ZERO);
+        for index in 0..CHUNK_SIZE_USIZE * CHUNK_SIZE_USIZE * CHUNK_SIZE_USIZE {
+            let coord = Chunk::index_to_coordinates(index);
+            padded.set_voxel(coord, if is_air(coord) { 0 } else { STONE });
+        }
+        padded
+    }
+
+    fn connected_pairs(visibility: ChunkVisibility) -> Vec<(usize, usize)> {
+        (0..6).flat_map(|a| (a + 1..6).map(move |b| (a, b))).filter(|&(a, b)| visibility.connects(a, b)).collect()
+    }
+
+    #[test]
+    fn solid_and_empty_chunks() {
+        let voxel_data_manager = test_voxel_data_manager();
+        assert_eq!(ChunkVisibility::from_padded_chunk(&padded_chunk(|_| false), &voxel_data_manager), ChunkVisibility::NONE);
+        assert_eq!(ChunkVisibility::from_padded_chunk(&padded_chunk(|_| true), &voxel_data_manager), ChunkVisibility::ALL);
+    }
+
+    #[test]
+    fn sealed_cave() {
+        // A hollow in the middle that doesn't reach any face
+        let voxel_data_manager = test_voxel_data_manager();
+        let padded = padded_chunk(|c| c.cmpge(glam::IVec3::splat(8)).all() && c.cmplt(glam::IVec3::splat(24)).all());
+        assert_eq!(ChunkVisibility::from_padded_chunk(&padded, &voxel_data_manager), ChunkVisibility::NONE);
+    }
+
+    #[test]
+    fn tunnels() {
+        let voxel_data_manager = test_voxel_data_manager();
+        // Straight through from -x to +x
+        let padded = padded_chunk(|c| c.y == 10 && c.z == 10);
+        assert_eq!(connected_pairs(ChunkVisibility::from_padded_chunk(&padded, &voxel_data_manager)), vec![(0, 1)]);
+        // Bending from -x up to +y
+        let padded = padded_chunk(|c| (c.y == 10 && c.z == 10 && c.x <= 10) || (c.x == 10 && c.z == 10 && c.y >= 10));
+        assert_eq!(connected_pairs(ChunkVisibility::from_padded_chunk(&padded, &voxel_data_manager)), vec![(0, 3)]);
+        // Two separate tunnels don't see into each other
+        let padded = padded_chunk(|c| (c.y == 5 && c.z == 5) || (c.x == 20 && c.y == 20));
+        assert_eq!(connected_pairs(ChunkVisibility::from_padded_chunk(&padded, &voxel_data_manager)), vec![(0, 1), (4, 5)]);
+    }
+
+    #[test]
+    fn sealed_chunk_blocks_the_view() {
+        let row = |middle: ChunkVisibility| HashMap::from([
+            (glam::ivec3(0, 0, 0), ChunkVisibility::ALL),
+            (glam::ivec3(1, 0, 0), middle),
+            (glam::ivec3(2, 0, 0), ChunkVisibility::ALL),
+        ]);
+        let sealed = row(ChunkVisibility::NONE);
+        let visible = find_visible_chunks(glam::ivec3(0, 0, 0), |p| sealed.get(&p)).unwrap();
+        // The sealed chunk itself can be seen, but not anything past it
+        assert!(visible.contains(&glam::ivec3(1, 0, 0)));
+        assert!(!visible.contains(&glam::ivec3(2, 0, 0)));
+
+        let mut tunnel = ChunkVisibility::NONE;
+        tunnel.connect(0, 1);
+        let tunnelled = row(tunnel);
+        let visible = find_visible_chunks(glam::ivec3(0, 0, 0), |p| tunnelled.get(&p)).unwrap();
+        assert!(visible.contains(&glam::ivec3(2, 0, 0)));
+
+        // Nothing to start from if the camera's chunk isn't loaded
+        assert!(find_visible_chunks(glam::ivec3(5, 0, 0), |p| sealed.get(&p)).is_none());
+    }
+}
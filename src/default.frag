@@ -12,6 +12,8 @@ out vec4 color;
 uniform sampler2DArray texture_array;
 
 uniform uint draw_mode;
+uniform float alpha_cutoff;
+uniform float opacity;
 
 void main() {
     //color = vec4(1.0, 1.0, 1.0, 1.0);
@@ -24,7 +26,8 @@ void main() {
     } else { // AO
         color = vec4(v_chunk_colour, 1) * vec4(vec3(1 - (v_ambient_occlusion*2.2)), 1);
     }
-    if (color.a == 0) {
+    if (color.a <= alpha_cutoff) {
         discard;
     }
+    color.a *= opacity;
 }
\ No newline at end of file
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+
+use glam::Vec3;
+
+use crate::{chunk::{ChunkPosition, VoxelPosition, VoxelID}, chunk_manager::ChunkManager};
+
+// How many ticks C4 waits before going off once it's been lit
+pub const C4_FUSE_TICKS: u64 = 80;
+// The range of fuses (in ticks) explosives get when they're set off by another explosion
+pub const CHAIN_FUSE_MIN: u64 = 10;
+pub const CHAIN_FUSE_MAX: u64 = 30;
+
+// How far a ray moves each step, and how much it's weakened by just travelling that far
+const RAY_STEP: f32 = 0.3;
+const RAY_FALLOFF: f32 = 0.75 * RAY_STEP;
+// Rays are cast at every point on the surface of a cube this many points across
+const RAY_GRID_SIZE: i32 = 16;
+
+// An explosive that's been lit and is waiting to go off
+#[derive(Debug, Clone, Copy)]
+pub struct PrimedExplosive {
+    pub position: VoxelPosition,
+    pub power: f32,
+    // The tick it goes off on
+    pub detonates_at: u64,
+}
+
+// What an explosion did
+#[derive(Debug, Clone)]
+pub struct ExplosionResult {
+    pub center: Vec3,
+    pub power: f32,
+    // Every voxel that was removed and what it used to be
+    pub destroyed: Vec<(VoxelPosition, VoxelID)>,
+    // Chunks that need their meshes rebuilding because of it
+    pub dirty_chunks: HashSet<ChunkPosition>,
+}
+
+// The directions rays are cast in, from the center of the explosion out to every point on the surface of a cube
+fn ray_directions() -> Vec<Vec3> {
+    let max = RAY_GRID_SIZE - 1;
+    let mut directions = vec![];
+    for x in 0..RAY_GRID_SIZE {
+        for y in 0..RAY_GRID_SIZE {
+            for z in 0..RAY_GRID_SIZE {
+                let on_surface = [x, y, z].iter().any(|&c| c == 0 || c == max);
+                if !on_surface { continue; }
+                let point = glam::vec3(x as f32, y as f32, z as f32) / max as f32 * 2.0 - 1.0;
+                directions.push(point.normalize());
+            }
+        }
+    }
+    directions
+}
+
+// Explosions cast rays out from their center, each ray starts with the explosion's power (give or take a bit)
+// and loses some every step, more when it goes through voxels with a higher blast resistance
+// Any voxel a ray gets through while it still has power left is destroyed
+impl ChunkManager {
+    // Blows up everything around center, the removed voxels are all changed in one go
+    // Explosives that get caught in it are lit with a short fuse rather than just being removed
+    pub fn explode(&mut self, center: Vec3, power: f32) -> ExplosionResult {
+        let mut destroyed: Vec<(VoxelPosition, VoxelID)> = vec![];
+        let mut seen: HashSet<VoxelPosition> = HashSet::new();
+        for direction in ray_directions() {
+            let mut intensity = power * self.tick_rng.range(0.7, 1.3);
+            let mut position = center;
+            while intensity > 0.0 {
+                let voxel_position = position.floor().as_ivec3();
+                // Rays stop at the edge of the loaded world
+                let Some(voxel_id) = self.get_voxel(voxel_position) else { break; };
+                if voxel_id != 0 {
+                    let blast_resistance = self.voxel_data_manager.get_voxel_data(voxel_id).blast_resistance;
+                    intensity -= (blast_resistance + RAY_STEP) * RAY_STEP;
+                    if intensity > 0.0 && seen.insert(voxel_position) {
+                        destroyed.push((voxel_position, voxel_id));
+                    }
+                }
+                position += direction * RAY_STEP;
+                intensity -= RAY_FALLOFF;
+            }
+        }
+
+        for &(voxel_position, voxel_id) in &destroyed {
+            let explosive_power = self.voxel_data_manager.get_voxel_data(voxel_id).explosive_power;
+            if explosive_power > 0.0 {
+                let fuse = self.tick_rng.range(CHAIN_FUSE_MIN, CHAIN_FUSE_MAX + 1);
+                self.prime_explosive(voxel_position, explosive_power, fuse);
+            }
+        }
+        let changes: Vec<(VoxelPosition, VoxelID)> = destroyed.iter().map(|&(p, _)| (p, 0)).collect();
+        let dirty_chunks = self.set_voxels(&changes);
+        ExplosionResult { center, power, destroyed, dirty_chunks }
+    }
+
+    // Lights the explosive at global_coord, it's removed from the world and goes off after its fuse
+    // Returns false if there isn't an explosive there
+    pub fn ignite(&mut self, global_coord: VoxelPosition) -> bool {
+        let Some(voxel_id) = self.get_voxel(global_coord) else { return false; };
+        let explosive_power = self.voxel_data_manager.get_voxel_data(voxel_id).explosive_power;
+        if explosive_power <= 0.0 {
+            return false;
+        }
+        self.set_voxel(global_coord, 0);
+        self.prime_explosive(global_coord, explosive_power, C4_FUSE_TICKS);
+        true
+    }
+
+    fn prime_explosive(&mut self, position: VoxelPosition, power: f32, fuse: u64) {
+        self.primed_explosives.push(PrimedExplosive { position, power, detonates_at: self.tick_count + fuse.max(1) });
+    }
+
+    // Sets off every explosive whose fuse has run out, in the order they were lit
+    pub fn tick_explosives(&mut self) {
+        let tick = self.tick_count;
+        let (ready, waiting): (Vec<PrimedExplosive>, Vec<PrimedExplosive>) =
+            std::mem::take(&mut self.primed_explosives).into_iter().partition(|e| e.detonates_at <= tick);
+        self.primed_explosives = waiting;
+        for explosive in ready {
+            let result = self.explode(explosive.position.as_vec3() + 0.5, explosive.power);
+            self.explosions.push(result);
+        }
+    }
+
+    // Takes the results of every explosion that's gone off during ticks since the last call
+    pub fn drain_explosions(&mut self) -> Vec<ExplosionResult> {
+        std::mem::take(&mut self.explosions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_behaviours, chunk::{Chunk, DEFAULT_VOXELS}, voxel_data_manager::VoxelDataManager};
+
+    // One chunk of air, with a wall of deep stone at x = 20 and dirt either side of the middle
+    fn test_world() -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        block_behaviours::register_default_behaviours(&mut voxel_data_manager);
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, 0);
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (dirt, deep_stone) = (ids("Dirt"), ids("Deep Stone"));
+        let mut voxels = DEFAULT_VOXELS;
+        for (index, voxel) in voxels.iter_mut().enumerate() {
+            let p = Chunk::index_to_coordinates(index);
+            let in_wall = (8..24).contains(&p.y) && (8..24).contains(&p.z);
+            if p.x == 20 && in_wall {
+                *voxel = deep_stone;
+            } else if (p.x == 15 || p.x == 22) && p.y == 16 && p.z == 16 {
+                *voxel = dirt;
+            }
+        }
+        chunk_manager.set_chunk(VoxelPosition::ZERO, voxels);
+        chunk_manager
+    }
+
+    #[test]
+    fn blast_resistance_stops_rays() {
+        let mut chunk_manager = test_world();
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (dirt, deep_stone) = (ids("Dirt"), ids("Deep Stone"));
+        let result = chunk_manager.explode(glam::vec3(16.5, 16.5, 16.5), 4.0);
+
+        assert_eq!(result.destroyed, vec![(glam::ivec3(15, 16, 16), dirt)]);
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(15, 16, 16)), Some(0));
+        // The wall soaks the blast up, so nothing behind it is touched
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(20, 16, 16)), Some(deep_stone));
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(22, 16, 16)), Some(dirt));
+    }
+
+    #[test]
+    fn explosives_in_the_blast_are_lit() {
+        let mut chunk_manager = test_world();
+        let c4 = chunk_manager.voxel_data_manager.get_id("C4").unwrap();
+        chunk_manager.set_voxel(glam::ivec3(17, 16, 16), c4);
+        chunk_manager.explode(glam::vec3(16.5, 16.5, 16.5), 4.0);
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(17, 16, 16)), Some(0));
+        assert_eq!(chunk_manager.primed_explosives.len(), 1);
+        let primed = chunk_manager.primed_explosives[0];
+        assert_eq!(primed.position, glam::ivec3(17, 16, 16));
+        assert!((CHAIN_FUSE_MIN..=CHAIN_FUSE_MAX).contains(&primed.detonates_at));
+    }
+}
@@ -0,0 +1,142 @@
+use glam::Vec3;
+
+use crate::{chunk::{VoxelPosition, VoxelID}, chunk_manager::ChunkManager, voxel_data_manager::VOXEL_TYPE_CUBE};
+
+// How many ticks a voxel waits after losing its support before it starts falling
+pub const FALL_DELAY_TICKS: u64 = 2;
+// Falling blocks are stepped once per tick, this is how long a tick is in seconds
+pub const FALLING_BLOCK_TIMESTEP: f32 = 0.05;
+// In voxels per second (squared)
+pub const FALLING_BLOCK_GRAVITY: f32 = 32.0;
+pub const FALLING_BLOCK_MAX_SPEED: f32 = 40.0;
+
+const DOWN: VoxelPosition = glam::ivec3(0, -1, 0);
+
+// A voxel that's come loose and is falling, it's not part of any chunk until it lands
+#[derive(Debug, Clone, Copy)]
+pub struct FallingBlock {
+    pub voxel_id: VoxelID,
+    // The corner with the lowest coordinates, x and z always stay lined up with the grid
+    pub position: Vec3,
+    // Only ever downwards (negative)
+    pub velocity: f32,
+}
+
+// Voxels with gravity set fall when there's nothing under them. They wait a couple of ticks, turn into a FallingBlock,
+// and get put back into the world when they hit something
+impl ChunkManager {
+    // Whether a falling block would be stopped by the voxel at global_coord, voxels that aren't loaded count as solid
+    pub fn blocks_falling(&self, global_coord: VoxelPosition) -> bool {
+        match self.get_voxel(global_coord) {
+            Some(voxel_id) => voxel_id != 0 && self.get_fluid(voxel_id).is_none(),
+            None => true,
+        }
+    }
+
+    // The neighbour update handler for voxels with gravity, they start falling shortly after losing their support
+    pub fn schedule_fall_check(&mut self, pos: VoxelPosition, _from: VoxelPosition) {
+        if !self.blocks_falling(pos + DOWN) {
+            self.schedule_tick(pos, FALL_DELAY_TICKS);
+        }
+    }
+
+    // The scheduled tick handler for voxels with gravity, takes the voxel out of the world if it's still unsupported
+    pub fn start_falling(&mut self, pos: VoxelPosition) {
+        if self.blocks_falling(pos + DOWN) { return; }
+        let Some(voxel_id) = self.get_voxel(pos) else { return; };
+        self.set_voxel(pos, 0);
+        self.falling_blocks.push(FallingBlock { voxel_id, position: pos.as_vec3(), velocity: 0.0 });
+    }
+
+    // Moves every falling block forward by one timestep, landing the ones that hit something
+    pub fn step_falling_blocks(&mut self) {
+        let falling_blocks = std::mem::take(&mut self.falling_blocks);
+        let mut still_falling = Vec::with_capacity(falling_blocks.len());
+        for mut block in falling_blocks {
+            block.velocity = (block.velocity - FALLING_BLOCK_GRAVITY * FALLING_BLOCK_TIMESTEP).max(-FALLING_BLOCK_MAX_SPEED);
+            let new_y = block.position.y + block.velocity * FALLING_BLOCK_TIMESTEP;
+            let current = block.position.floor().as_ivec3();
+            // Check every voxel the bottom of the block moves into, so fast blocks can't go through thin floors
+            let landed_on = ((new_y.floor() as i32)..current.y).rev()
+                .map(|y| glam::ivec3(current.x, y, current.z))
+                .find(|&p| self.blocks_falling(p));
+            match landed_on {
+                Some(floor) => self.land_falling_block(block.voxel_id, floor - DOWN),
+                None => {
+                    block.position.y = new_y;
+                    still_falling.push(block);
+                }
+            }
+        }
+        // Anything that started falling while landing (e.g. sand stacked on the sand that just landed) goes at the end
+        still_falling.append(&mut self.falling_blocks);
+        self.falling_blocks = still_falling;
+    }
+
+    // Puts a falling block back into the world at pos
+    // If it landed on something that isn't a full block (like grass) or its spot's been taken, it breaks and vanishes
+    fn land_falling_block(&mut self, voxel_id: VoxelID, pos: VoxelPosition) {
+        let on_full_block = self.get_voxel(pos + DOWN).is_none_or(|v| self.voxel_data_manager.get_voxel_type(v) == VOXEL_TYPE_CUBE);
+        if on_full_block && !self.blocks_falling(pos) {
+            self.set_voxel(pos, voxel_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_behaviours, chunk::{Chunk, DEFAULT_VOXELS}, voxel_data_manager::VoxelDataManager};
+
+    // One chunk with a stone floor along y = 0, with random ticks turned off
+    fn test_world() -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        block_behaviours::register_default_behaviours(&mut voxel_data_manager);
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, 0);
+        chunk_manager.random_tick_speed = 0;
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        let mut voxels = DEFAULT_VOXELS;
+        for (index, voxel) in voxels.iter_mut().enumerate() {
+            if Chunk::index_to_coordinates(index).y == 0 {
+                *voxel = stone;
+            }
+        }
+        chunk_manager.set_chunk(VoxelPosition::ZERO, voxels);
+        chunk_manager
+    }
+
+    fn run_ticks(chunk_manager: &mut ChunkManager, ticks: u32) {
+        for _ in 0..ticks {
+            chunk_manager.tick();
+        }
+    }
+
+    #[test]
+    fn sand_falls_and_lands_on_the_floor() {
+        let mut chunk_manager = test_world();
+        let sand = chunk_manager.voxel_data_manager.get_id("Sand").unwrap();
+        chunk_manager.set_voxel(glam::ivec3(8, 20, 8), sand);
+        run_ticks(&mut chunk_manager, FALL_DELAY_TICKS as u32 + 1);
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 20, 8)), Some(0));
+        assert_eq!(chunk_manager.falling_blocks.len(), 1);
+
+        run_ticks(&mut chunk_manager, 100);
+        assert!(chunk_manager.falling_blocks.is_empty());
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 1, 8)), Some(sand));
+    }
+
+    #[test]
+    fn sand_breaks_when_it_lands_on_a_cross_block() {
+        let mut chunk_manager = test_world();
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (sand, grass, dirt) = (ids("Sand"), ids("Grass"), ids("Dirt"));
+        // Planted in dirt so it doesn't break on its own
+        chunk_manager.set_voxel(glam::ivec3(8, 0, 8), dirt);
+        chunk_manager.set_voxel(glam::ivec3(8, 1, 8), grass);
+        chunk_manager.set_voxel(glam::ivec3(8, 20, 8), sand);
+        run_ticks(&mut chunk_manager, 100);
+        assert!(chunk_manager.falling_blocks.is_empty());
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 2, 8)), Some(0));
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 1, 8)), Some(grass));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{chunk::{VoxelPosition, VoxelID, VoxelState}, chunk_manager::ChunkManager, voxel_data_manager::VOXEL_TYPE_CROSS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FluidKind {
+    Water,
+    Lava,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fluid {
+    pub kind: FluidKind,
+    // How much the level goes up for every voxel the fluid spreads sideways, it stops once it's past MAX_FLUID_LEVEL
+    pub level_drop: u8,
+    // How many ticks it waits before flowing
+    pub tick_delay: u64,
+    // Whether flowing fluid between two sources turns into a source
+    pub creates_sources: bool,
+}
+
+// Fluid levels are stored in the voxel's state - 0 is a source, and it goes up the further the fluid has spread
+pub const MAX_FLUID_LEVEL: u8 = 7;
+// Set on fluid that's being fed from above, it's always full height and spreads like a source when it lands
+pub const FLUID_FALLING: VoxelState = 8;
+
+const HORIZONTAL_NEIGHBOURS: [VoxelPosition; 4] = [
+    glam::ivec3(-1, 0, 0),
+    glam::ivec3( 1, 0, 0),
+    glam::ivec3( 0, 0,-1),
+    glam::ivec3( 0, 0, 1),
+];
+const UP: VoxelPosition = glam::ivec3(0, 1, 0);
+const DOWN: VoxelPosition = glam::ivec3(0, -1, 0);
+
+impl Fluid {
+    pub const WATER: Fluid = Fluid { kind: FluidKind::Water, level_drop: 1, tick_delay: 5, creates_sources: true };
+    pub const LAVA: Fluid = Fluid { kind: FluidKind::Lava, level_drop: 2, tick_delay: 30, creates_sources: false };
+
+    pub fn level(state: VoxelState) -> u8 {
+        state & MAX_FLUID_LEVEL
+    }
+    pub fn is_falling(state: VoxelState) -> bool {
+        state & FLUID_FALLING != 0
+    }
+    pub fn is_source(state: VoxelState) -> bool {
+        state == 0
+    }
+    // How high the surface of the fluid is in its voxel, from 0 to 1
+    pub fn height(state: VoxelState) -> f32 {
+        if Fluid::is_falling(state) {
+            return 1.0;
+        }
+        (8 - Fluid::level(state)) as f32 / 9.0
+    }
+}
+
+// Fluid simulation, fluids only flow when they get a scheduled tick (which they ask for whenever a voxel next to them changes)
+impl ChunkManager {
+    pub fn get_fluid(&self, voxel_id: VoxelID) -> Option<Fluid> {
+        self.voxel_data_manager.get_voxel_data(voxel_id).fluid
+    }
+    fn get_fluid_at(&self, global_coord: VoxelPosition) -> Option<Fluid> {
+        self.get_voxel(global_coord).and_then(|v| self.get_fluid(v))
+    }
+
+    // The neighbour update handler for fluids, they flow once their tick delay is up
+    pub fn schedule_fluid_tick(&mut self, pos: VoxelPosition, _from: VoxelPosition) {
+        if let Some(fluid) = self.get_fluid_at(pos) {
+            self.schedule_tick(pos, fluid.tick_delay);
+        }
+    }
+
+    // The scheduled tick handler for fluids
+    pub fn update_fluid(&mut self, pos: VoxelPosition) {
+        let Some(voxel_id) = self.get_voxel(pos) else { return; };
+        let Some(fluid) = self.get_fluid(voxel_id) else { return; };
+        let mut state = self.get_voxel_state(pos).unwrap_or(0);
+
+        // Lava touching water hardens, sources into stone and flowing lava into cobblestone
+        if fluid.kind == FluidKind::Lava {
+            for offset in [UP].into_iter().chain(HORIZONTAL_NEIGHBOURS) {
+                if self.get_fluid_at(pos + offset).map(|f| f.kind) == Some(FluidKind::Water) {
+                    let hardened = if Fluid::is_source(state) { "Stone" } else { "Cobblestone" };
+                    self.set_voxel(pos, self.voxel_data_manager.get_id(hardened).unwrap_or(0));
+                    return;
+                }
+            }
+        }
+
+        // Flowing fluid changes its level depending on what's feeding it, sources stay as they are
+        if !Fluid::is_source(state) {
+            match self.get_flowing_state(pos, voxel_id, fluid) {
+                // Nothing's feeding it anymore so it dries up
+                None => {
+                    self.set_voxel(pos, 0);
+                    return;
+                }
+                Some(new_state) if new_state != state => {
+                    self.set_voxel_with_state(pos, voxel_id, new_state);
+                    state = new_state;
+                }
+                _ => {}
+            }
+        }
+
+        self.spread_fluid(pos, voxel_id, fluid, state);
+    }
+
+    // Works out the state flowing fluid should have from its neighbours, None if it isn't being fed
+    fn get_flowing_state(&self, pos: VoxelPosition, voxel_id: VoxelID, fluid: Fluid) -> Option<VoxelState> {
+        if self.get_voxel(pos + UP) == Some(voxel_id) {
+            return Some(FLUID_FALLING);
+        }
+        let mut sources = 0;
+        let mut lowest_level: Option<u8> = None;
+        for offset in HORIZONTAL_NEIGHBOURS {
+            if self.get_voxel(pos + offset) != Some(voxel_id) { continue; }
+            let neighbour_state = self.get_voxel_state(pos + offset).unwrap_or(0);
+            if Fluid::is_source(neighbour_state) {
+                sources += 1;
+            }
+            // Falling fluid spreads out as if it were a source
+            let level = if Fluid::is_falling(neighbour_state) { 0 } else { Fluid::level(neighbour_state) };
+            lowest_level = Some(lowest_level.map_or(level, |l| l.min(level)));
+        }
+        // Fluid between two sources becomes one, as long as it's got something to sit on
+        if fluid.creates_sources && sources >= 2 {
+            let below = self.get_voxel(pos + DOWN);
+            let supported = below.is_some_and(|b| self.voxel_data_manager.is_opaque_cube(b)) ||
+                (below == Some(voxel_id) && self.get_voxel_state(pos + DOWN) == Some(0));
+            if supported {
+                return Some(0);
+            }
+        }
+        lowest_level.map(|l| l + fluid.level_drop).filter(|&l| l <= MAX_FLUID_LEVEL)
+    }
+
+    // Flows down if it can, otherwise out to the sides
+    fn spread_fluid(&mut self, pos: VoxelPosition, voxel_id: VoxelID, fluid: Fluid, state: VoxelState) {
+        if self.flow_into(pos + DOWN, voxel_id, fluid, FLUID_FALLING) {
+            return;
+        }
+        // Already flowing down, so it doesn't spread out until it lands
+        if self.get_voxel(pos + DOWN) == Some(voxel_id) && self.get_voxel_state(pos + DOWN) != Some(0) {
+            return;
+        }
+        let level = if Fluid::is_falling(state) { 0 } else { Fluid::level(state) };
+        let next_level = level + fluid.level_drop;
+        if next_level > MAX_FLUID_LEVEL {
+            return;
+        }
+        for offset in HORIZONTAL_NEIGHBOURS {
+            self.flow_into(pos + offset, voxel_id, fluid, next_level);
+        }
+    }
+
+    // Tries to put fluid in a voxel, returns whether it did
+    fn flow_into(&mut self, target: VoxelPosition, voxel_id: VoxelID, fluid: Fluid, new_state: VoxelState) -> bool {
+        let Some(target_id) = self.get_voxel(target) else { return false; };
+        if target_id == voxel_id {
+            // Only replace weaker fluid of the same kind
+            let target_state = self.get_voxel_state(target).unwrap_or(0);
+            let weaker = !Fluid::is_source(target_state) && !Fluid::is_falling(target_state) &&
+                (Fluid::is_falling(new_state) || Fluid::level(target_state) > Fluid::level(new_state));
+            if !weaker {
+                return false;
+            }
+        } else if let Some(target_fluid) = self.get_fluid(target_id) {
+            // Lava flowing into water turns it to stone, water flowing into lava is left for the lava to deal with
+            if fluid.kind == FluidKind::Lava && target_fluid.kind == FluidKind::Water {
+                return self.set_voxel(target, self.voxel_data_manager.get_id("Stone").unwrap_or(0));
+            }
+            return false;
+        } else if target_id != 0 && self.voxel_data_manager.get_voxel_type(target_id) != VOXEL_TYPE_CROSS {
+            // Fluids wash away cross shaped voxels like grass, but anything else stops them
+            return false;
+        }
+        self.set_voxel_with_state(target, voxel_id, new_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block_behaviours, chunk::{Chunk, DEFAULT_VOXELS}, voxel_data_manager::VoxelDataManager};
+
+    // One chunk with a stone floor along y = 0, with random ticks turned off
+    fn test_world() -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        block_behaviours::register_default_behaviours(&mut voxel_data_manager);
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, 0);
+        chunk_manager.random_tick_speed = 0;
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        let mut voxels = DEFAULT_VOXELS;
+        for (index, voxel) in voxels.iter_mut().enumerate() {
+            if Chunk::index_to_coordinates(index).y == 0 {
+                *voxel = stone;
+            }
+        }
+        chunk_manager.set_chunk(VoxelPosition::ZERO, voxels);
+        chunk_manager
+    }
+
+    fn run_ticks(chunk_manager: &mut ChunkManager, ticks: u32) {
+        for _ in 0..ticks {
+            chunk_manager.tick();
+        }
+    }
+
+    // The voxel and level along +x from a source on the floor at (8, 1, 16)
+    fn spread_along_x(chunk_manager: &ChunkManager, distance: i32) -> (VoxelID, Option<u8>) {
+        let pos = glam::ivec3(8 + distance, 1, 16);
+        let voxel_id = chunk_manager.get_voxel(pos).unwrap();
+        (voxel_id, chunk_manager.get_fluid(voxel_id).map(|_| Fluid::level(chunk_manager.get_voxel_state(pos).unwrap())))
+    }
+
+    #[test]
+    fn water_spreads_seven_voxels() {
+        let mut chunk_manager = test_world();
+        let water = chunk_manager.voxel_data_manager.get_id("Water").unwrap();
+        chunk_manager.set_voxel(glam::ivec3(8, 1, 16), water);
+        run_ticks(&mut chunk_manager, 200);
+        for distance in 0..=MAX_FLUID_LEVEL as i32 {
+            assert_eq!(spread_along_x(&chunk_manager, distance), (water, Some(distance as u8)));
+        }
+        assert_eq!(spread_along_x(&chunk_manager, MAX_FLUID_LEVEL as i32 + 1), (0, None));
+        // It only spreads along the floor
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 2, 16)), Some(0));
+        assert!(chunk_manager.get_chunk(VoxelPosition::ZERO).unwrap().scheduled_ticks.is_empty(), "water should have settled");
+    }
+
+    #[test]
+    fn lava_spreads_less_and_slower() {
+        let mut chunk_manager = test_world();
+        let lava = chunk_manager.voxel_data_manager.get_id("Lava").unwrap();
+        chunk_manager.set_voxel(glam::ivec3(8, 1, 16), lava);
+        // Not far enough for it to have reached a second voxel
+        run_ticks(&mut chunk_manager, Fluid::LAVA.tick_delay as u32 * 2 - 1);
+        assert_eq!(spread_along_x(&chunk_manager, 2), (0, None));
+        run_ticks(&mut chunk_manager, 400);
+        for (distance, level) in [(0, 0), (1, 2), (2, 4), (3, 6)] {
+            assert_eq!(spread_along_x(&chunk_manager, distance), (lava, Some(level)));
+        }
+        assert_eq!(spread_along_x(&chunk_manager, 4), (0, None));
+    }
+
+    #[test]
+    fn lava_hardens_next_to_water() {
+        let mut chunk_manager = test_world();
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (water, lava, stone, cobblestone) = (ids("Water"), ids("Lava"), ids("Stone"), ids("Cobblestone"));
+        // Water poured next to a lava source turns it to stone
+        chunk_manager.set_voxel(glam::ivec3(8, 1, 4), lava);
+        chunk_manager.set_voxel(glam::ivec3(9, 1, 4), water);
+        run_ticks(&mut chunk_manager, 100);
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 1, 4)), Some(stone));
+
+        // and flowing lava turns to cobblestone
+        chunk_manager.set_voxel(glam::ivec3(8, 1, 16), lava);
+        run_ticks(&mut chunk_manager, 400);
+        assert_eq!(spread_along_x(&chunk_manager, 3), (lava, Some(6)));
+        chunk_manager.set_voxel(glam::ivec3(13, 1, 16), water);
+        run_ticks(&mut chunk_manager, 100);
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(11, 1, 16)), Some(cobblestone));
+        assert_eq!(chunk_manager.get_voxel(glam::ivec3(8, 1, 16)), Some(lava));
+    }
+}
@@ -0,0 +1,119 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::chunk::{ChunkPosition, CHUNK_SIZE};
+
+// The six planes around everything a camera can see, each stored as (normal, distance) with the normal pointing inwards
+// so a point is inside a plane when normal.dot(point) + distance >= 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6], // left right bottom top near far
+}
+
+impl Frustum {
+    // Pulls the planes out of a perspective * view matrix (Gribb & Hartmann)
+    // The near and far planes use OpenGL's -w to w clip range, since that's what actually gets drawn
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (r0, r1, r2, r3) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2].map(|p| p / p.truncate().length());
+        Self { planes }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|p| p.truncate().dot(point) + p.w >= 0.0)
+    }
+
+    // Whether any of a box might be visible. Boxes near the corners of the frustum can be counted as visible when
+    // they're not, but a visible box is never counted as hidden
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|p| {
+            // The corner furthest along the plane's normal, if that's outside then all of the box is
+            let normal = p.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(corner) + p.w >= 0.0
+        })
+    }
+
+    // Whether any of a chunk is inside the frustum
+    pub fn intersects_chunk(&self, chunk_position: ChunkPosition) -> bool {
+        let min = (chunk_position * CHUNK_SIZE).as_vec3();
+        self.intersects_aabb(min, min + CHUNK_SIZE as f32)
+    }
+
+    // Filters a list of chunks down to the ones that might be on screen
+    pub fn cull_chunks<'a>(&self, chunk_positions: impl IntoIterator<Item = &'a ChunkPosition>) -> Vec<ChunkPosition> {
+        chunk_positions.into_iter().filter(|&&p| self.intersects_chunk(p)).copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::FOV;
+
+    // The same matrices the camera makes, with a square window
+    fn frustum(position: Vec3, yaw: f32) -> Frustum {
+        let view = Mat4::from_euler(glam::EulerRot::XYZ, 0.0, yaw, 0.0) * Mat4::from_translation(-position);
+        Frustum::from_matrix(Mat4::perspective_lh(FOV, 1.0, 0.01, 1024.0) * view)
+    }
+
+    #[test]
+    fn far_plane() {
+        // With a near plane this close, f32 rounding in the matrix puts where it actually clips about a voxel short
+        // of 1024, and the plane has to match that rather than the far distance it was asked for
+        let matrix = Mat4::perspective_lh(FOV, 1.0, 0.01, 1024.0);
+        let far = -matrix.w_axis.z / (matrix.z_axis.z - 1.0);
+        assert!((1022.0..=1024.0).contains(&far));
+        let f = frustum(Vec3::ZERO, 0.0);
+        assert!(f.contains_point(glam::vec3(0.0, 0.0, far - 0.1)));
+        assert!(!f.contains_point(glam::vec3(0.0, 0.0, far + 0.1)));
+        // Chunk 32 starts 1024 voxels along z, so half a voxel back it's just past the far plane
+        let f = frustum(glam::vec3(16.0, 16.0, -0.5), 0.0);
+        assert!(f.intersects_chunk(glam::ivec3(0, 0, 31)));
+        assert!(!f.intersects_chunk(glam::ivec3(0, 0, 32)));
+        // And a voxel and a half forward it's just inside
+        let f = frustum(glam::vec3(16.0, 16.0, 1.5), 0.0);
+        assert!(f.intersects_chunk(glam::ivec3(0, 0, 32)));
+        assert!(!f.intersects_chunk(glam::ivec3(0, 0, 33)));
+    }
+
+    #[test]
+    fn near_plane() {
+        let f = frustum(Vec3::ZERO, 0.0);
+        assert!(f.contains_point(glam::vec3(0.0, 0.0, 0.011)));
+        assert!(!f.contains_point(glam::vec3(0.0, 0.0, -0.001)));
+        // The near plane comes from OpenGL's -w clip range rather than the 0 the matrix maps the near distance to,
+        // so it's at about half the near distance. That's a little generous but never culls anything that's drawn
+        assert!(f.contains_point(glam::vec3(0.0, 0.0, 0.006)));
+        assert!(!f.contains_point(glam::vec3(0.0, 0.0, 0.004)));
+    }
+
+    #[test]
+    fn chunks_around_the_camera() {
+        // In the middle of chunk 0, looking along +z
+        let f = frustum(glam::vec3(16.0, 16.0, 16.0), 0.0);
+        // The chunk the camera's in straddles the near plane
+        assert!(f.intersects_chunk(glam::ivec3(0, 0, 0)));
+        assert!(f.intersects_chunk(glam::ivec3(0, 0, 1)));
+        // Behind the camera
+        assert!(!f.intersects_chunk(glam::ivec3(0, 0, -1)));
+        assert!(!f.intersects_chunk(glam::ivec3(0, 0, -2)));
+        // Off to the sides, above and below
+        assert!(!f.intersects_chunk(glam::ivec3(10, 0, 1)));
+        assert!(!f.intersects_chunk(glam::ivec3(-10, 0, 1)));
+        assert!(!f.intersects_chunk(glam::ivec3(0, 10, 1)));
+        assert!(!f.intersects_chunk(glam::ivec3(0, -10, 1)));
+        // Far enough away that the same offset is inside the view
+        assert!(f.intersects_chunk(glam::ivec3(10, 0, 20)));
+
+        let chunks = [glam::ivec3(0, 0, 0), glam::ivec3(0, 0, -1), glam::ivec3(10, 0, 1), glam::ivec3(0, 0, 5)];
+        assert_eq!(f.cull_chunks(&chunks), vec![glam::ivec3(0, 0, 0), glam::ivec3(0, 0, 5)]);
+    }
+
+    #[test]
+    fn turning_around() {
+        // Looking along -z, so what was behind is in front
+        let f = frustum(glam::vec3(16.0, 16.0, 16.0), std::f32::consts::PI);
+        assert!(f.intersects_chunk(glam::ivec3(0, 0, -2)));
+        assert!(!f.intersects_chunk(glam::ivec3(0, 0, 2)));
+    }
+}
@@ -0,0 +1,262 @@
+use glium::glutin::{dpi::PhysicalSize, event::MouseScrollDelta};
+
+use crate::{chunk::{VoxelID, VoxelPosition}, chunk_manager::ChunkManager, camera::{FlyCamera, OrbitCamera}, window_context::{Keyboard, Mouse},
+    player_controller::{PlayerController, MovementMode, REACH}, inventory::{self, Hotbar}, input_actions::{Action, ActionMap, Actions},
+    input_recording::{InputEvent, InputFrame}, game_loop::FixedTimestep, block_highlight, physics::CollisionShape};
+
+// Blocks can only be placed over air and fluids
+pub fn can_place_at(chunk_manager: &ChunkManager, pos: VoxelPosition) -> bool {
+    chunk_manager.get_voxel(pos).is_some_and(|v| v == 0 || chunk_manager.get_fluid(v).is_some())
+}
+
+// Everything the game's logic keeps between frames, apart from the world, the camera, the player and the hotbar
+// None of it needs a window, so a recording plays out the same with or without one
+pub struct GameLogic {
+    pub action_map: ActionMap,
+    // Everything that can be placed, for cycling through with CycleBlock
+    pub palette: Vec<VoxelID>,
+    pub kb: Keyboard,
+    pub m: Mouse,
+    // Mouse-look is measured from the middle of the window, this is kept up to date by InputEvent::Resized
+    pub window_size: PhysicalSize<u32>,
+    pub looking: bool,
+    // Set while orbiting around something, the camera goes back to normal where the orbit left it
+    pub orbit: Option<OrbitCamera>,
+    // Movement's simulated 60 times a second and the world ticks 20 times a second, no matter the framerate
+    pub simulation: FixedTimestep,
+    pub world_ticks: FixedTimestep,
+    // Where the camera was before the last step, for smoothing it out between steps
+    pub previous_camera_position: glam::Vec3,
+}
+
+impl GameLogic {
+    pub fn new(action_map: ActionMap, palette: Vec<VoxelID>, window_size: PhysicalSize<u32>, camera_position: glam::Vec3) -> Self {
+        Self { action_map, palette, kb: Keyboard::new(), m: Mouse::new(), window_size, looking: false, orbit: None,
+            simulation: FixedTimestep::new(60.0), world_ticks: FixedTimestep::new(20.0), previous_camera_position: camera_position }
+    }
+
+    // Plays a frame's events in the order they happened
+    // The camera turns for every cursor move rather than once a frame, the same as it would've done live
+    pub fn apply_input(&mut self, cam: &mut FlyCamera, frame: &InputFrame) {
+        for event in &frame.events {
+            event.apply(&mut self.kb, &mut self.m);
+            match *event {
+                InputEvent::CursorMoved(position) if self.looking => cam.look(&position, self.window_size),
+                InputEvent::Resized(size) => self.window_size = size,
+                _ => (),
+            }
+        }
+    }
+
+    // Runs one frame of everything that changes the world, the camera or the hotbar
+    // Anything to do with the window (cursor, fullscreen, what's drawn) is left to whoever's calling, using the actions
+    // that are returned
+    pub fn step_frame(&mut self, chunk_manager: &mut ChunkManager, cam: &mut FlyCamera, player: &mut PlayerController, hotbar: &mut Hotbar, frame: &InputFrame) -> Actions {
+        self.apply_input(cam, frame);
+        let actions = self.action_map.resolve(&self.kb, &self.m);
+
+        if actions.action_pressed(Action::ToggleMouseLook) {
+            self.looking = !self.looking;
+        }
+
+        // Choose a block with the number keys or the scroll wheel (which zooms while orbiting), Q swaps the held block
+        for (slot, action) in inventory::SELECT_SLOT_ACTIONS.iter().enumerate() {
+            if actions.action_pressed(*action) {
+                hotbar.select(slot);
+            }
+        }
+        if self.orbit.is_none() {
+            let scroll = match self.m.get_scroll_delta() {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(p) => p.y as f32,
+            };
+            if scroll != 0.0 {
+                hotbar.scroll(-scroll.signum() as i32);
+            }
+        }
+        if actions.action_pressed(Action::CycleBlock) {
+            hotbar.cycle_selected(&self.palette, 1);
+        }
+
+        // Light any C4 close to the camera
+        if actions.action_pressed(Action::Ignite) {
+            let cam_voxel = cam.camera.position.floor().as_ivec3();
+            for x in -8..=8 {
+                for y in -8..=8 {
+                    for z in -8..=8 {
+                        chunk_manager.ignite(cam_voxel + glam::ivec3(x, y, z));
+                    }
+                }
+            }
+        }
+
+        if actions.action_pressed(Action::ToggleMovementMode) {
+            player.toggle_mode();
+        }
+
+        if actions.action_pressed(Action::ToggleOrbit) {
+            self.orbit = match self.orbit {
+                Some(_) => None,
+                None => {
+                    // Start off looking at everything around the camera
+                    let cam_voxel = cam.camera.position.floor().as_ivec3();
+                    let mut new_orbit = OrbitCamera::new(cam.camera.position);
+                    new_orbit.yaw = cam.camera.rotation.y;
+                    new_orbit.frame_box(cam_voxel - 8, cam_voxel + 8);
+                    self.looking = false;
+                    Some(new_orbit)
+                }
+            };
+        }
+
+        // Orbiting follows the mouse, so it's done every frame rather than every step
+        if let Some(orbit) = &mut self.orbit {
+            orbit.handle_mouse(&self.m, &actions);
+            orbit.apply(&mut cam.camera);
+            self.previous_camera_position = cam.camera.position;
+        }
+
+        // Break and place whatever's being looked at, orbiting uses the mouse to move the camera so it can't edit
+        let is_target = |v| v != 0 && chunk_manager.get_fluid(v).is_none();
+        let edit_target = self.orbit.is_none().then(|| chunk_manager.raycast(cam.camera.position, cam.camera.forward(), REACH, is_target)).flatten();
+        if let Some(hit) = edit_target {
+            if actions.action_pressed(Action::BreakBlock) {
+                chunk_manager.set_voxel(hit.voxel, 0);
+            }
+            let place_pos = hit.voxel + hit.normal;
+            if let (true, Some(voxel_id)) = (actions.action_pressed(Action::PlaceBlock), hotbar.selected_voxel()) {
+                // Don't put anything solid inside the player
+                let solid = chunk_manager.voxel_data_manager.get_voxel_data(voxel_id).collision_shape != CollisionShape::None;
+                let place_box = block_highlight::selection_box(&chunk_manager.voxel_data_manager, voxel_id).offset(place_pos.as_vec3());
+                if can_place_at(chunk_manager, place_pos) && !(solid && player.mode == MovementMode::Walk && player.get_aabb(&cam.camera).intersects(&place_box)) {
+                    chunk_manager.place_voxel(place_pos, voxel_id);
+                }
+            }
+            if actions.action_pressed(Action::PickBlock) {
+                if let Some(voxel_id) = chunk_manager.get_voxel(hit.voxel) {
+                    hotbar.pick_block(voxel_id);
+                }
+            }
+        }
+        self.simulation.update(frame.deltatime, |dt| {
+            self.previous_camera_position = cam.camera.position;
+            match (&self.orbit, player.mode) {
+                (Some(_), _) => (),
+                (None, MovementMode::Fly) => {
+                    cam.handle_movement(&actions, &dt);
+                    // The brush only works while flying, otherwise it'd dig out the ground under the player
+                    // It always digs, the hotbar's block is only for placing
+                    for i in 0..27 {
+                        let cam_head_pos = cam.camera.position.as_ivec3() + glam::ivec3(i % 3, (i / 9)-1, (i / 3)%3)-(3/2);
+                        chunk_manager.set_voxel(cam_head_pos, 0);
+                    }
+                }
+                (None, MovementMode::Walk) => player.handle_movement(&mut cam.camera, &actions, dt, chunk_manager),
+            }
+            self.world_ticks.update(dt, |_| chunk_manager.tick());
+        });
+
+        // Pressed and released only last the frame they happened in
+        self.kb.clear();
+        self.m.clear();
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glium::glutin::{dpi::PhysicalPosition, event::{ElementState, VirtualKeyCode}};
+
+    use super::*;
+    use crate::{voxel_data_manager::VoxelDataManager, block_behaviours, input_recording::{InputRecording, InputReplay}, inventory::build_palette};
+
+    // The game's voxels and behaviours, with the chunks around the camera generated
+    fn test_world(seed: u64) -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        block_behaviours::register_default_behaviours(&mut voxel_data_manager);
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, seed);
+        for x in -1..=0 {
+            for y in -1..=0 {
+                for z in -1..=0 {
+                    chunk_manager.add_chunk(glam::ivec3(x, y, z));
+                }
+            }
+        }
+        chunk_manager
+    }
+
+    // Plays a recording from the start with nothing but the recording to go on, and a hotbar full of blocks
+    fn replay(recording: &InputRecording) -> (ChunkManager, FlyCamera, Hotbar) {
+        let mut chunk_manager = test_world(recording.seed);
+        let mut cam = FlyCamera::new();
+        let mut player = PlayerController::new();
+        let palette = build_palette(&chunk_manager.voxel_data_manager);
+        let mut hotbar = Hotbar::from_palette(&palette);
+        let mut logic = GameLogic::new(ActionMap::default(), palette, PhysicalSize::new(1280, 720), cam.camera.position);
+        let mut replay = InputReplay::new(recording.clone());
+        while let Some(frame) = replay.next_frame() {
+            logic.step_frame(&mut chunk_manager, &mut cam, &mut player, &mut hotbar, frame);
+        }
+        (chunk_manager, cam, hotbar)
+    }
+
+    // Looks around while flying forward with the brush digging, and swaps the held block
+    fn test_recording() -> InputRecording {
+        let key = |key, state| InputEvent::Key(key, state);
+        let mut recording = InputRecording::new(1234);
+        for i in 0..60 {
+            let mut events = vec![InputEvent::CursorMoved(PhysicalPosition::new(400.0 + (i % 5) as f64 * 7.0, 297.0))];
+            if i == 0 {
+                events.extend([InputEvent::Resized(PhysicalSize::new(800, 600)), key(VirtualKeyCode::E, ElementState::Pressed),
+                    key(VirtualKeyCode::W, ElementState::Pressed), key(VirtualKeyCode::Q, ElementState::Pressed)]);
+            }
+            recording.frames.push(InputFrame { deltatime: if i % 2 == 0 { 0.016 } else { 0.017 }, events });
+        }
+        recording
+    }
+
+    #[test]
+    fn replays_come_out_the_same() {
+        let recording = test_recording();
+        let (first_world, first_cam, _) = replay(&recording);
+        // The second run goes through the file format too, so a saved recording plays back the same
+        let (second_world, second_cam, _) = replay(&InputRecording::parse(&recording.to_recording_string()).unwrap());
+
+        assert_eq!(first_cam.camera.position, second_cam.camera.position);
+        assert_eq!(first_cam.camera.rotation, second_cam.camera.rotation);
+        assert_ne!(first_cam.camera.rotation.y, 0.0);
+        let untouched = test_world(recording.seed);
+        let mut changed = false;
+        for (position, chunk) in &first_world.chunks {
+            assert!(chunk.get_voxels() == second_world.chunks[position].get_voxels(), "chunk {} came out differently", position);
+            changed |= chunk.get_voxels() != untouched.chunks[position].get_voxels();
+        }
+        assert!(changed, "the recording didn't change the world");
+    }
+
+    #[test]
+    fn brush_digs_whatever_block_is_held() {
+        let (world, cam, hotbar) = replay(&test_recording());
+        assert!(hotbar.selected_voxel().is_some_and(|v| v != 0));
+        for i in 0..27 {
+            let brush_pos = cam.camera.position.as_ivec3() + glam::ivec3(i % 3, (i / 9)-1, (i / 3)%3)-(3/2);
+            assert_eq!(world.get_voxel(brush_pos), Some(0), "the brush left something at {}", brush_pos);
+        }
+    }
+
+    #[test]
+    fn mouse_look_uses_every_cursor_event() {
+        let mut cam = FlyCamera::new();
+        let mut logic = GameLogic::new(ActionMap::default(), vec![], PhysicalSize::new(1280, 720), cam.camera.position);
+        logic.looking = true;
+        // The cursor goes back to the middle after every move, so both of these count, measured from the middle of the
+        // recorded window size rather than the one it started with
+        logic.apply_input(&mut cam, &InputFrame { deltatime: 0.016, events: vec![
+            InputEvent::Resized(PhysicalSize::new(800, 600)),
+            InputEvent::CursorMoved(PhysicalPosition::new(410.0, 300.0)),
+            InputEvent::CursorMoved(PhysicalPosition::new(420.0, 300.0)),
+        ]});
+        assert!((cam.camera.rotation.y - (std::f32::consts::TAU - 0.03)).abs() < 1e-5);
+        assert_eq!(cam.camera.rotation.x, 0.0);
+    }
+}
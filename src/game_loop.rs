@@ -0,0 +1,121 @@
+use std::time::Instant;
+
+// Frames longer than this are cut short, so after a stall (dragging the window, a breakpoint) the game slows down
+// for a moment rather than running hundreds of steps to catch up
+pub const MAX_FRAME_TIME: f32 = 0.25;
+
+// Measures how long each frame took, using Instant since it never goes backwards (SystemTime can)
+pub struct Clock {
+    last_tick: Instant,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self { last_tick: Instant::now() }
+    }
+    // Seconds since the last tick (or since the clock was made)
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_tick).as_secs_f32();
+        self.last_tick = now;
+        elapsed
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Runs a simulation in steps of the same length however fast frames are coming in
+// Frame time builds up in the accumulator and gets spent a step at a time, whatever's left over is how far
+// between the last step and the next one the frame is, which rendering can use to smooth things out
+pub struct FixedTimestep {
+    pub step_length: f32,
+    accumulator: f32,
+    // How many steps have been run altogether
+    pub step_count: u64,
+}
+
+impl FixedTimestep {
+    // rate is in steps per second
+    pub fn new(rate: f32) -> Self {
+        Self { step_length: 1.0 / rate, accumulator: 0.0, step_count: 0 }
+    }
+
+    // Adds a frame's worth of time and runs step (with the step length) for every step that's due
+    // Nothing here needs a window, so a simulation can be driven by made up frame times
+    pub fn update(&mut self, frame_time: f32, mut step: impl FnMut(f32)) -> u32 {
+        self.accumulator += frame_time.clamp(0.0, MAX_FRAME_TIME);
+        let mut steps = 0;
+        while self.accumulator >= self.step_length {
+            self.accumulator -= self.step_length;
+            self.step_count += 1;
+            steps += 1;
+            step(self.step_length);
+        }
+        steps
+    }
+
+    // How far it is from the last step to the next one, from 0 to 1
+    // Things should be drawn this far between where they were before the last step and where they are now
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step_length).clamp(0.0, 1.0)
+    }
+    // Simulated time, in seconds
+    pub fn elapsed(&self) -> f64 {
+        self.step_count as f64 * self.step_length as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 8 steps a second keeps all the times below exact in floating point
+    const RATE: f32 = 8.0;
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut timestep = FixedTimestep::new(RATE);
+        assert_eq!(timestep.update(0.1875, |dt| assert_eq!(dt, 0.125)), 1);
+        assert_eq!(timestep.alpha(), 0.5);
+        // Not enough for a step by itself, but it is with what was left over last time
+        assert_eq!(timestep.update(0.0625, |_| ()), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.update(0.0625, |_| ()), 0);
+        assert_eq!(timestep.step_count, 2);
+        assert_eq!(timestep.elapsed(), 0.25);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let mut timestep = FixedTimestep::new(60.0);
+        for i in 0..1000 {
+            timestep.update(0.001 + (i % 37) as f32 * 0.0013, |_| ());
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha was {}", alpha);
+        }
+    }
+
+    #[test]
+    fn long_frames_are_cut_short() {
+        let mut timestep = FixedTimestep::new(RATE);
+        let mut steps = 0;
+        assert_eq!(timestep.update(10.0, |_| steps += 1), (MAX_FRAME_TIME * RATE) as u32);
+        assert_eq!(steps, 2);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn zero_and_negative_frames_do_nothing() {
+        let mut timestep = FixedTimestep::new(RATE);
+        timestep.update(0.0625, |_| ());
+        assert_eq!(timestep.update(0.0, |_| panic!("stepped on an empty frame")), 0);
+        assert_eq!(timestep.update(-1.0, |_| panic!("stepped on a negative frame")), 0);
+        // Time going backwards doesn't eat into what's already built up either
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.step_count, 0);
+    }
+}
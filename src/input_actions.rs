@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+
+use glium::glutin::event::{VirtualKeyCode, MouseButton};
+
+use crate::window_context::{Keyboard, Mouse};
+
+// Lists every variant of an enum along with its name, so they can be written to and read from the config file
+macro_rules! named_variants {
+    ($name:ident: $type:ty { $($variant:ident),* $(,)? }) => {
+        const $name: &[(&str, $type)] = &[$((stringify!($variant), <$type>::$variant)),*];
+    };
+}
+
+// Everything the player can do, game code asks about these rather than about keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    Sprint,
+    PlaceBlock,
+    BreakBlock,
+    CycleBlock,
+    PickBlock,
+    SelectSlot1,
+    SelectSlot2,
+    SelectSlot3,
+    SelectSlot4,
+    SelectSlot5,
+    SelectSlot6,
+    SelectSlot7,
+    SelectSlot8,
+    SelectSlot9,
+    Ignite,
+    ToggleMovementMode,
+    ToggleOrbit,
+    OrbitRotate,
+    OrbitPan,
+    ToggleMouseLook,
+    ToggleWireframe,
+    ToggleBackfaceCulling,
+    ToggleChunkColours,
+    CycleDrawMode,
+    ToggleDebugOverlay,
+    ToggleFullscreen,
+    Quit,
+}
+
+named_variants!(ACTION_NAMES: Action {
+    MoveForward, MoveBackward, MoveLeft, MoveRight, Jump, Crouch, Sprint, PlaceBlock, BreakBlock, CycleBlock, PickBlock,
+    SelectSlot1, SelectSlot2, SelectSlot3, SelectSlot4, SelectSlot5, SelectSlot6, SelectSlot7, SelectSlot8, SelectSlot9, Ignite,
+    ToggleMovementMode, ToggleOrbit, OrbitRotate, OrbitPan, ToggleMouseLook, ToggleWireframe, ToggleBackfaceCulling,
+    ToggleChunkColours, CycleDrawMode, ToggleDebugOverlay, ToggleFullscreen, Quit,
+});
+
+named_variants!(KEY_NAMES: VirtualKeyCode {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+    Snapshot, Scroll, Pause, Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space,
+    Compose, Caret, Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadDivide, NumpadDecimal, NumpadComma, NumpadEnter, NumpadEquals, NumpadMultiply, NumpadSubtract,
+    AbntC1, AbntC2, Apostrophe, Apps, Asterisk, At, Ax, Backslash, Calculator, Capital, Colon, Comma, Convert, Equals,
+    Grave, Kana, Kanji, LAlt, LBracket, LControl, LShift, LWin, Mail, MediaSelect, MediaStop, Minus, Mute, MyComputer,
+    NavigateForward, NavigateBackward, NextTrack, NoConvert, OEM102, Period, PlayPause, Plus, Power, PrevTrack, RAlt,
+    RBracket, RControl, RShift, RWin, Semicolon, Slash, Sleep, Stop, Sysrq, Tab, Underline, Unlabeled, VolumeDown,
+    VolumeUp, Wake, WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch, WebStop, Yen, Copy, Paste, Cut,
+});
+
+const MOUSE_PREFIX: &str = "Mouse";
+
+// A key or mouse button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+impl Input {
+    pub fn parse(name: &str) -> Option<Input> {
+        if let Some(button) = name.strip_prefix(MOUSE_PREFIX) {
+            return match button {
+                "Left" => Some(Input::Mouse(MouseButton::Left)),
+                "Right" => Some(Input::Mouse(MouseButton::Right)),
+                "Middle" => Some(Input::Mouse(MouseButton::Middle)),
+                _ => None,
+            };
+        }
+        KEY_NAMES.iter().find(|(n, _)| *n == name).map(|&(_, key)| Input::Key(key))
+    }
+    pub fn name(&self) -> String {
+        match self {
+            Input::Key(key) => KEY_NAMES.iter().find(|(_, k)| k == key).map_or_else(|| format!("{:?}", key), |(n, _)| n.to_string()),
+            Input::Mouse(button) => format!("{}{:?}", MOUSE_PREFIX, button),
+        }
+    }
+
+    fn held(&self, kb: &Keyboard, m: &Mouse) -> bool {
+        match *self {
+            Input::Key(key) => kb.key_held(key),
+            Input::Mouse(button) => m.button_held(button),
+        }
+    }
+    fn pressed(&self, kb: &Keyboard, m: &Mouse) -> bool {
+        match *self {
+            Input::Key(key) => kb.key_pressed(key),
+            Input::Mouse(button) => m.button_pressed(button),
+        }
+    }
+    fn released(&self, kb: &Keyboard, m: &Mouse) -> bool {
+        match *self {
+            Input::Key(key) => kb.key_released(key),
+            Input::Mouse(button) => m.button_released(button),
+        }
+    }
+}
+
+// An input, along with any inputs that have to be held down with it (e.g. LAlt+Return)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub modifiers: Vec<Input>,
+    pub input: Input,
+}
+
+impl Binding {
+    pub fn new(input: Input) -> Self {
+        Self { modifiers: vec![], input }
+    }
+    pub fn key(key: VirtualKeyCode) -> Self {
+        Self::new(Input::Key(key))
+    }
+    pub fn mouse(button: MouseButton) -> Self {
+        Self::new(Input::Mouse(button))
+    }
+    pub fn chord(modifiers: &[VirtualKeyCode], key: VirtualKeyCode) -> Self {
+        Self { modifiers: modifiers.iter().map(|&k| Input::Key(k)).collect(), input: Input::Key(key) }
+    }
+
+    // Reads a binding like "LAlt+Return" or "MouseLeft"
+    pub fn parse(text: &str) -> Option<Binding> {
+        let mut inputs = text.split('+').map(|part| Input::parse(part.trim())).collect::<Option<Vec<Input>>>()?;
+        let input = inputs.pop()?;
+        Some(Binding { modifiers: inputs, input })
+    }
+    pub fn name(&self) -> String {
+        self.modifiers.iter().chain([&self.input]).map(|i| i.name()).collect::<Vec<String>>().join("+")
+    }
+
+    fn modifiers_held(&self, kb: &Keyboard, m: &Mouse) -> bool {
+        self.modifiers.iter().all(|i| i.held(kb, m))
+    }
+}
+
+// Which bindings set off which actions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionMap {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl ActionMap {
+    // Nothing bound to anything
+    pub fn empty() -> Self {
+        Self { bindings: HashMap::new() }
+    }
+
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+    // Replaces everything bound to an action
+    pub fn rebind(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+    pub fn get_bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |b| b.as_slice())
+    }
+
+    // Works out which actions are held, pressed and released this frame
+    // An action's pressed or released when its input is, as long as the modifiers are held down too
+    pub fn resolve(&self, kb: &Keyboard, m: &Mouse) -> Actions {
+        let mut actions = Actions::default();
+        for (&action, bindings) in &self.bindings {
+            for binding in bindings {
+                if !binding.modifiers_held(kb, m) {
+                    // Letting go of the input still releases the action, even if the modifiers went first
+                    if binding.input.released(kb, m) { actions.released.insert(action); }
+                    continue;
+                }
+                if binding.input.held(kb, m) { actions.held.insert(action); }
+                if binding.input.pressed(kb, m) { actions.pressed.insert(action); }
+                if binding.input.released(kb, m) { actions.released.insert(action); }
+            }
+        }
+        actions
+    }
+
+    // The config file has one action per line, with its bindings separated by commas, e.g.
+    // MoveForward = W, Up
+    // ToggleFullscreen = LAlt+Return
+    // Lines starting with # are ignored, and actions that aren't in the file keep their default bindings
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut map = ActionMap::default();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let error = |message: &str| format!("Line {}: {} ({})", line_number + 1, message, line);
+            let (action_name, bindings_text) = line.split_once('=').ok_or_else(|| error("missing ="))?;
+            let action = ACTION_NAMES.iter().find(|(n, _)| *n == action_name.trim()).map(|&(_, a)| a).ok_or_else(|| error("unknown action"))?;
+            let bindings = bindings_text.split(',').map(str::trim).filter(|b| !b.is_empty())
+                .map(|b| Binding::parse(b).ok_or_else(|| error(&format!("unknown input {}", b))))
+                .collect::<Result<Vec<Binding>, String>>()?;
+            map.rebind(action, bindings);
+        }
+        Ok(map)
+    }
+    pub fn to_config_string(&self) -> String {
+        let mut text = String::from("# Action = Binding, Binding... (use + for chords, e.g. LAlt+Return, and MouseLeft/MouseRight/MouseMiddle for buttons)\n");
+        for (name, action) in ACTION_NAMES {
+            let bindings: Vec<String> = self.get_bindings(*action).iter().map(|b| b.name()).collect();
+            text += &format!("{} = {}\n", name, bindings.join(", "));
+        }
+        text
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ActionMap::parse(&text)
+    }
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_config_string())
+    }
+    // Loads the bindings from path, if there's no file there the defaults are saved to it
+    pub fn load_or_default(path: &str) -> Self {
+        if !std::path::Path::new(path).exists() {
+            let map = ActionMap::default();
+            if let Err(e) = map.save(path) {
+                println!("Couldn't save default controls to {}!! {}", path, e);
+            }
+            return map;
+        }
+        ActionMap::load(path).unwrap_or_else(|e| {
+            println!("Couldn't load controls from {}, using the defaults!! {}", path, e);
+            ActionMap::default()
+        })
+    }
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        let mut map = ActionMap::empty();
+        for (action, key) in [
+            (Action::MoveForward, VirtualKeyCode::W),
+            (Action::MoveBackward, VirtualKeyCode::S),
+            (Action::MoveLeft, VirtualKeyCode::A),
+            (Action::MoveRight, VirtualKeyCode::D),
+            (Action::Jump, VirtualKeyCode::Space),
+            (Action::Crouch, VirtualKeyCode::LShift),
+            (Action::Sprint, VirtualKeyCode::LControl),
+            (Action::CycleBlock, VirtualKeyCode::Q),
+            (Action::SelectSlot1, VirtualKeyCode::Key1),
+            (Action::SelectSlot2, VirtualKeyCode::Key2),
+            (Action::SelectSlot3, VirtualKeyCode::Key3),
+            (Action::SelectSlot4, VirtualKeyCode::Key4),
+            (Action::SelectSlot5, VirtualKeyCode::Key5),
+            (Action::SelectSlot6, VirtualKeyCode::Key6),
+            (Action::SelectSlot7, VirtualKeyCode::Key7),
+            (Action::SelectSlot8, VirtualKeyCode::Key8),
+            (Action::SelectSlot9, VirtualKeyCode::Key9),
+            (Action::Ignite, VirtualKeyCode::F),
+            (Action::ToggleMovementMode, VirtualKeyCode::V),
+            (Action::ToggleOrbit, VirtualKeyCode::O),
+            (Action::ToggleMouseLook, VirtualKeyCode::E),
+            (Action::ToggleWireframe, VirtualKeyCode::C),
+            (Action::ToggleBackfaceCulling, VirtualKeyCode::B),
+            (Action::ToggleChunkColours, VirtualKeyCode::X),
+            (Action::CycleDrawMode, VirtualKeyCode::Z),
+            (Action::ToggleDebugOverlay, VirtualKeyCode::F3),
+            (Action::Quit, VirtualKeyCode::Escape),
+        ] {
+            map.bind(action, Binding::key(key));
+        }
+        map.bind(Action::PlaceBlock, Binding::mouse(MouseButton::Right));
+        map.bind(Action::BreakBlock, Binding::mouse(MouseButton::Left));
+        map.bind(Action::PickBlock, Binding::mouse(MouseButton::Middle));
+        map.bind(Action::OrbitRotate, Binding::mouse(MouseButton::Left));
+        map.bind(Action::OrbitPan, Binding::mouse(MouseButton::Middle));
+        map.bind(Action::ToggleFullscreen, Binding::chord(&[VirtualKeyCode::LAlt], VirtualKeyCode::Return));
+        map
+    }
+}
+
+// Which actions are happening this frame
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Actions {
+    held: HashSet<Action>,
+    pressed: HashSet<Action>,
+    released: HashSet<Action>,
+}
+
+impl Actions {
+    pub fn action_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+    pub fn action_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    pub fn action_released(&self, action: Action) -> bool {
+        self.released.contains(&action)
+    }
+}
@@ -0,0 +1,166 @@
+use glium::glutin::{event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode}, dpi::{PhysicalPosition, PhysicalSize}};
+
+use crate::{window_context::{Keyboard, Mouse}, input_actions::Input};
+
+// Something the player did, in the order it happened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Key(VirtualKeyCode, ElementState),
+    Button(MouseButton, ElementState),
+    CursorMoved(PhysicalPosition<f64>),
+    Scroll(MouseScrollDelta),
+    // The window's size, recorded at the start and whenever it changes since mouse-look is measured from the middle of it
+    Resized(PhysicalSize<u32>),
+}
+
+impl InputEvent {
+    // Updates the keyboard and mouse, anything else the event changes is up to GameLogic::apply_input
+    pub fn apply(&self, kb: &mut Keyboard, m: &mut Mouse) {
+        match *self {
+            InputEvent::Key(key, state) => kb.process_key(key, state),
+            InputEvent::Button(button, state) => m.process_input(state, button),
+            InputEvent::CursorMoved(position) => m.set_pos(position),
+            InputEvent::Scroll(delta) => m.set_scroll_delta(delta),
+            InputEvent::Resized(_) => (),
+        }
+    }
+
+    // One event per line, e.g. "key W pressed", "button Left released", "cursor 640 360", "scroll line 0 -1" or "resize 1280 720"
+    // Floats are written out in full, so they come back exactly the same
+    fn to_line(self) -> String {
+        let state_name = |state: ElementState| match state {
+            ElementState::Pressed => "pressed",
+            ElementState::Released => "released",
+        };
+        match self {
+            InputEvent::Key(key, state) => format!("key {} {}", Input::Key(key).name(), state_name(state)),
+            InputEvent::Button(button, state) => format!("button {:?} {}", button, state_name(state)),
+            InputEvent::CursorMoved(p) => format!("cursor {} {}", p.x, p.y),
+            InputEvent::Scroll(MouseScrollDelta::LineDelta(x, y)) => format!("scroll line {} {}", x, y),
+            InputEvent::Scroll(MouseScrollDelta::PixelDelta(p)) => format!("scroll pixel {} {}", p.x, p.y),
+            InputEvent::Resized(size) => format!("resize {} {}", size.width, size.height),
+        }
+    }
+    fn parse(line: &str) -> Option<InputEvent> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let state = |name: &str| match name {
+            "pressed" => Some(ElementState::Pressed),
+            "released" => Some(ElementState::Released),
+            _ => None,
+        };
+        match parts.as_slice() {
+            ["key", key, s] => match Input::parse(key)? {
+                Input::Key(key) => Some(InputEvent::Key(key, state(s)?)),
+                Input::Mouse(_) => None,
+            },
+            ["button", button, s] => match Input::parse(&format!("Mouse{}", button))? {
+                Input::Mouse(button) => Some(InputEvent::Button(button, state(s)?)),
+                Input::Key(_) => None,
+            },
+            ["cursor", x, y] => Some(InputEvent::CursorMoved(PhysicalPosition::new(x.parse().ok()?, y.parse().ok()?))),
+            ["scroll", "line", x, y] => Some(InputEvent::Scroll(MouseScrollDelta::LineDelta(x.parse().ok()?, y.parse().ok()?))),
+            ["scroll", "pixel", x, y] => Some(InputEvent::Scroll(MouseScrollDelta::PixelDelta(PhysicalPosition::new(x.parse().ok()?, y.parse().ok()?)))),
+            ["resize", width, height] => Some(InputEvent::Resized(PhysicalSize::new(width.parse().ok()?, height.parse().ok()?))),
+            _ => None,
+        }
+    }
+}
+
+// Everything that happened in one frame, and how long the frame took
+// Live input and replays both go through GameLogic::step_frame as one of these
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputFrame {
+    pub deltatime: f32,
+    pub events: Vec<InputEvent>,
+}
+
+// A whole session of input, along with the world seed so the world comes out the same when it's replayed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputRecording {
+    pub seed: u64,
+    pub frames: Vec<InputFrame>,
+}
+
+impl InputRecording {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, frames: vec![] }
+    }
+
+    // The file starts with "seed <seed>", then each frame is "frame <deltatime>" followed by its events
+    pub fn to_recording_string(&self) -> String {
+        let mut text = format!("seed {}\n", self.seed);
+        for frame in &self.frames {
+            text += &format!("frame {}\n", frame.deltatime);
+            for event in &frame.events {
+                text += &event.to_line();
+                text.push('\n');
+            }
+        }
+        text
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate().map(|(n, l)| (n + 1, l.trim())).filter(|(_, l)| !l.is_empty());
+        let seed = lines.next()
+            .and_then(|(_, l)| l.strip_prefix("seed "))
+            .and_then(|s| s.trim().parse().ok())
+            .ok_or("Recording doesn't start with a seed")?;
+        let mut recording = InputRecording::new(seed);
+        for (line_number, line) in lines {
+            if let Some(deltatime) = line.strip_prefix("frame ") {
+                let deltatime = deltatime.trim().parse().map_err(|_| format!("Line {}: bad frame time ({})", line_number, line))?;
+                recording.frames.push(InputFrame { deltatime, events: vec![] });
+                continue;
+            }
+            let event = InputEvent::parse(line).ok_or_else(|| format!("Line {}: unknown event ({})", line_number, line))?;
+            recording.frames.last_mut().ok_or_else(|| format!("Line {}: event before the first frame", line_number))?.events.push(event);
+        }
+        Ok(recording)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        InputRecording::parse(&text)
+    }
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_recording_string())
+    }
+}
+
+// Records each frame's input as it's played
+pub struct InputRecorder {
+    pub recording: InputRecording,
+}
+
+impl InputRecorder {
+    pub fn new(seed: u64) -> Self {
+        Self { recording: InputRecording::new(seed) }
+    }
+
+    pub fn record_frame(&mut self, frame: &InputFrame) {
+        // The mouse ignores other buttons, and they can't be written out anyway
+        let events = frame.events.iter().filter(|e| !matches!(e, InputEvent::Button(MouseButton::Other(_), _))).copied().collect();
+        self.recording.frames.push(InputFrame { deltatime: frame.deltatime, events });
+    }
+}
+
+// Plays a recording back a frame at a time
+pub struct InputReplay {
+    pub recording: InputRecording,
+    next_frame: usize,
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        Self { recording, next_frame: 0 }
+    }
+
+    // The next frame's input, or None once the recording's run out
+    pub fn next_frame(&mut self) -> Option<&InputFrame> {
+        let frame = self.recording.frames.get(self.next_frame)?;
+        self.next_frame += 1;
+        Some(frame)
+    }
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.recording.frames.len()
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{chunk::VoxelID, voxel_data_manager::VoxelDataManager, input_actions::Action};
+
+pub const HOTBAR_SIZE: usize = 9;
+// The action that selects each slot
+pub const SELECT_SLOT_ACTIONS: [Action; HOTBAR_SIZE] = [
+    Action::SelectSlot1, Action::SelectSlot2, Action::SelectSlot3, Action::SelectSlot4, Action::SelectSlot5,
+    Action::SelectSlot6, Action::SelectSlot7, Action::SelectSlot8, Action::SelectSlot9,
+];
+
+// Every voxel that can be placed, in registry order (everything but air)
+pub fn build_palette(voxel_data_manager: &VoxelDataManager) -> Vec<VoxelID> {
+    (1..voxel_data_manager.len()).map(|id| id as VoxelID).collect()
+}
+
+// The blocks the player has to hand, and which one they're holding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotbar {
+    pub slots: [Option<VoxelID>; HOTBAR_SIZE],
+    pub selected: usize,
+}
+
+impl Hotbar {
+    pub fn new() -> Self {
+        Self { slots: [None; HOTBAR_SIZE], selected: 0 }
+    }
+    // Starts off with the first blocks in the palette
+    pub fn from_palette(palette: &[VoxelID]) -> Self {
+        let mut hotbar = Hotbar::new();
+        for (slot, &voxel_id) in hotbar.slots.iter_mut().zip(palette) {
+            *slot = Some(voxel_id);
+        }
+        hotbar
+    }
+
+    // The block in the selected slot, None if it's empty
+    pub fn selected_voxel(&self) -> Option<VoxelID> {
+        self.slots[self.selected]
+    }
+    pub fn select(&mut self, slot: usize) {
+        self.selected = slot.min(HOTBAR_SIZE - 1);
+    }
+    // Moves the selection along, wrapping round at either end
+    pub fn scroll(&mut self, amount: i32) {
+        self.selected = (self.selected as i32 + amount).rem_euclid(HOTBAR_SIZE as i32) as usize;
+    }
+
+    // Swaps the block in the selected slot for the next (or previous) one in the palette
+    pub fn cycle_selected(&mut self, palette: &[VoxelID], direction: i32) {
+        if palette.is_empty() { return; }
+        let next = match self.selected_voxel().and_then(|v| palette.iter().position(|&p| p == v)) {
+            Some(index) => (index as i32 + direction).rem_euclid(palette.len() as i32) as usize,
+            None => 0,
+        };
+        self.slots[self.selected] = Some(palette[next]);
+    }
+
+    // Pick block: selects voxel_id if it's already in the hotbar, otherwise puts it in the selected slot if that's
+    // empty, then the first empty slot, and if there's no room it replaces the selected block
+    pub fn pick_block(&mut self, voxel_id: VoxelID) {
+        if let Some(slot) = self.slots.iter().position(|&s| s == Some(voxel_id)) {
+            self.selected = slot;
+            return;
+        }
+        if self.selected_voxel().is_some() {
+            if let Some(slot) = self.slots.iter().position(|s| s.is_none()) {
+                self.selected = slot;
+            }
+        }
+        self.slots[self.selected] = Some(voxel_id);
+    }
+
+    // Saved by name rather than ID, so it still works if voxels are added or moved around, e.g.
+    // selected = 2
+    // 1 = Stone
+    // 2 =
+    pub fn to_config_string(&self, voxel_data_manager: &VoxelDataManager) -> String {
+        let mut text = format!("selected = {}\n", self.selected + 1);
+        for (index, slot) in self.slots.iter().enumerate() {
+            let name = slot.map(|v| voxel_data_manager.get_name(v)).unwrap_or_default();
+            text += &format!("{} = {}\n", index + 1, name);
+        }
+        text
+    }
+    // Names that aren't in the registry (any more) leave their slot empty rather than failing
+    pub fn parse(text: &str, voxel_data_manager: &VoxelDataManager) -> Result<Self, String> {
+        let mut hotbar = Hotbar::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let error = |message: &str| format!("Line {}: {} ({})", line_number + 1, message, line);
+            let (key, value) = line.split_once('=').ok_or_else(|| error("missing ="))?;
+            let (key, value) = (key.trim(), value.trim());
+            // Slots are numbered from 1, like the keys that select them
+            let number: usize = if key == "selected" { value.parse() } else { key.parse() }.ok()
+                .filter(|n| (1..=HOTBAR_SIZE).contains(n))
+                .ok_or_else(|| error("bad slot"))?;
+            if key == "selected" {
+                hotbar.select(number - 1);
+            } else {
+                hotbar.slots[number - 1] = voxel_data_manager.get_id(value).filter(|&v| v != 0);
+            }
+        }
+        Ok(hotbar)
+    }
+
+    pub fn load(path: &str, voxel_data_manager: &VoxelDataManager) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Hotbar::parse(&text, voxel_data_manager)
+    }
+    pub fn save(&self, path: &str, voxel_data_manager: &VoxelDataManager) -> std::io::Result<()> {
+        std::fs::write(path, self.to_config_string(voxel_data_manager))
+    }
+    // Loads the hotbar from path, or fills it from the palette if there isn't one saved
+    pub fn load_or_default(path: &str, voxel_data_manager: &VoxelDataManager) -> Self {
+        if !std::path::Path::new(path).exists() {
+            return Hotbar::from_palette(&build_palette(voxel_data_manager));
+        }
+        Hotbar::load(path, voxel_data_manager).unwrap_or_else(|e| {
+            println!("Couldn't load the hotbar from {}, using the default!! {}", path, e);
+            Hotbar::from_palette(&build_palette(voxel_data_manager))
+        })
+    }
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_voxel_data_manager() -> VoxelDataManager {
+        VoxelDataManager::with_default_voxels(&mut vec![])
+    }
+
+    #[test]
+    fn palette_leaves_out_air() {
+        let voxel_data_manager = test_voxel_data_manager();
+        let palette = build_palette(&voxel_data_manager);
+        assert!(!palette.contains(&0));
+        assert_eq!(palette.len(), voxel_data_manager.len() - 1);
+        assert_eq!(palette[0], voxel_data_manager.get_id("Grass Block").unwrap());
+    }
+
+    #[test]
+    fn selecting_is_kept_in_range() {
+        let mut hotbar = Hotbar::from_palette(&[1, 2, 3]);
+        hotbar.select(2);
+        assert_eq!((hotbar.selected, hotbar.selected_voxel()), (2, Some(3)));
+        hotbar.select(3);
+        assert_eq!(hotbar.selected_voxel(), None);
+        hotbar.select(100);
+        assert_eq!(hotbar.selected, HOTBAR_SIZE - 1);
+    }
+
+    #[test]
+    fn scrolling_wraps_round() {
+        let mut hotbar = Hotbar::new();
+        hotbar.scroll(-1);
+        assert_eq!(hotbar.selected, HOTBAR_SIZE - 1);
+        hotbar.scroll(1);
+        assert_eq!(hotbar.selected, 0);
+        hotbar.scroll(HOTBAR_SIZE as i32 * 2 + 3);
+        assert_eq!(hotbar.selected, 3);
+    }
+
+    #[test]
+    fn cycling_goes_through_the_palette() {
+        let palette = [1, 2, 3];
+        let mut hotbar = Hotbar::new();
+        // An empty slot starts from the beginning
+        hotbar.cycle_selected(&palette, 1);
+        assert_eq!(hotbar.selected_voxel(), Some(1));
+        hotbar.cycle_selected(&palette, -1);
+        assert_eq!(hotbar.selected_voxel(), Some(3));
+        hotbar.cycle_selected(&palette, 1);
+        assert_eq!(hotbar.selected_voxel(), Some(1));
+        // Nothing to cycle through, so the slot's left alone
+        hotbar.cycle_selected(&[], 1);
+        assert_eq!(hotbar.selected_voxel(), Some(1));
+        assert_eq!(hotbar.slots[1..], [None; HOTBAR_SIZE - 1]);
+    }
+
+    #[test]
+    fn picking_a_block_already_in_the_hotbar_selects_it() {
+        let mut hotbar = Hotbar::from_palette(&[1, 2, 3]);
+        hotbar.pick_block(3);
+        assert_eq!(hotbar.selected, 2);
+        assert_eq!(hotbar, Hotbar { selected: 2, ..Hotbar::from_palette(&[1, 2, 3]) });
+    }
+
+    #[test]
+    fn picked_blocks_go_in_an_empty_slot() {
+        let mut hotbar = Hotbar::from_palette(&[1, 2, 3]);
+        // The selected slot if it's empty
+        hotbar.select(5);
+        hotbar.pick_block(7);
+        assert_eq!((hotbar.selected, hotbar.slots[5]), (5, Some(7)));
+        // Otherwise the first empty one, rather than replacing what's held
+        hotbar.select(0);
+        hotbar.pick_block(8);
+        assert_eq!((hotbar.selected, hotbar.slots[3]), (3, Some(8)));
+        assert_eq!(hotbar.slots[0], Some(1));
+    }
+
+    #[test]
+    fn picked_blocks_replace_the_selected_one_when_the_hotbar_is_full() {
+        let mut hotbar = Hotbar::from_palette(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        hotbar.select(4);
+        hotbar.pick_block(10);
+        assert_eq!(hotbar.selected, 4);
+        assert_eq!(hotbar.slots, [1, 2, 3, 4, 10, 6, 7, 8, 9].map(Some));
+    }
+
+    #[test]
+    fn config_round_trip() {
+        let voxel_data_manager = test_voxel_data_manager();
+        let mut hotbar = Hotbar::from_palette(&build_palette(&voxel_data_manager));
+        hotbar.slots[4] = None;
+        hotbar.select(6);
+        let text = hotbar.to_config_string(&voxel_data_manager);
+        assert_eq!(Hotbar::parse(&text, &voxel_data_manager), Ok(hotbar));
+    }
+
+    #[test]
+    fn unknown_names_leave_their_slot_empty() {
+        let voxel_data_manager = test_voxel_data_manager();
+        let stone = voxel_data_manager.get_id("Stone").unwrap();
+        let hotbar = Hotbar::parse("# saved hotbar\nselected = 3\n1 = Stone\n2 = Unobtainium\n3 = Air\n", &voxel_data_manager).unwrap();
+        assert_eq!(hotbar.selected, 2);
+        assert_eq!(hotbar.slots[..3], [Some(stone), None, None]);
+        assert!(Hotbar::parse("10 = Stone", &voxel_data_manager).is_err());
+        assert!(Hotbar::parse("1 Stone", &voxel_data_manager).is_err());
+    }
+}
@@ -3,4 +3,28 @@ pub mod window_context;
 pub mod chunk_manager;
 pub mod chunk;
 pub mod chunk_mesh;
-pub mod voxel_data_manager;
\ No newline at end of file
+pub mod padded_chunk;
+pub mod voxel_data_manager;
+pub mod fluid;
+pub mod tick_scheduler;
+pub mod block_updates;
+pub mod block_behaviours;
+pub mod world_statistics;
+pub mod explosion;
+pub mod falling_block;
+pub mod physics;
+pub mod player_controller;
+pub mod frustum;
+pub mod chunk_visibility;
+pub mod chunk_lod;
+pub mod packed_vertex;
+pub mod input_actions;
+pub mod input_recording;
+pub mod game_logic;
+pub mod game_loop;
+pub mod block_highlight;
+pub mod inventory;
+pub mod ui;
+pub mod metrics;
+pub mod block_icons;
+pub mod textures;
\ No newline at end of file
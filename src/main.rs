@@ -1,11 +1,11 @@
-use std::{time::SystemTime, vec, collections::HashMap, str::CharIndices};
+use std::{vec, collections::HashMap, str::CharIndices};
 use glam;
 use bracket_noise::prelude::*;
 use bracket_random::prelude::*;
 
 use glium::{glutin::{event_loop, event::ElementState, window::CursorGrabMode}, Surface, Blend};
 
-use voxel_builder::{chunk::{Convert, Chunk, self, ChunkPosition, VoxelPosition}, chunk_manager::ChunkManager, chunk_mesh::{self, ChunkVertex, ChunkMesh}, window_context, camera::{self, FlyCamera}, voxel_data_manager::{VoxelDataManager, VoxelData}};
+use voxel_builder::{chunk::{self, Chunk, ChunkPosition, VoxelPosition, Convert}, chunk_manager::ChunkManager, chunk_mesh::{self, ChunkVertex, ChunkMesh, ChunkMeshLayers}, camera::{self, FlyCamera}, player_controller::PlayerController, voxel_data_manager::{VoxelDataManager, VoxelData}, block_behaviours, chunk_visibility::{self, ChunkVisibility}, chunk_lod::LodPolicy, packed_vertex::{self, GpuChunkVertex}, input_actions::{Action, ActionMap}, input_recording::{InputEvent, InputFrame, InputRecorder, InputRecording, InputReplay}, game_logic::{self, GameLogic}, game_loop::Clock, block_highlight, player_controller::REACH, inventory::{self, Hotbar}, ui::{self, Ui}, metrics::Metrics, block_icons::{self, IconAtlas}, textures};
 
 #[macro_use]
 extern crate glium;
@@ -17,6 +17,49 @@ struct Vertex {
     tex_coords: [f32; 2],
 }
 
+// Everything needed to draw a chunk
+struct ChunkRenderData {
+    // Opaque, cutout and translucent
+    layers: [(glium::VertexBuffer<GpuChunkVertex>, glium::IndexBuffer<u32>); 3],
+    // Kept around so the translucent faces can be re-sorted when the camera moves
+    translucent_mesh: ChunkMesh,
+    visibility: ChunkVisibility,
+    // Which level of detail the meshes were built at, and which neighbours were at a lower one
+    lod: u32,
+    coarser_neighbours: u8,
+    colour: u32,
+}
+
+impl ChunkRenderData {
+    fn new(display: &glium::Display, mut meshes: ChunkMeshLayers, chunk_pos: ChunkPosition, camera_pos: glam::Vec3, lod: u32, coarser_neighbours: u8, colour: u32) -> Self {
+        meshes.translucent.sort_back_to_front(camera_pos - (chunk_pos * chunk::CHUNK_SIZE).as_vec3());
+        Self {
+            layers: [upload_chunk_mesh(display, &meshes.opaque), upload_chunk_mesh(display, &meshes.cutout), upload_chunk_mesh(display, &meshes.translucent)],
+            translucent_mesh: meshes.translucent,
+            visibility: meshes.visibility,
+            lod,
+            coarser_neighbours,
+            colour,
+        }
+    }
+    // Sorts the translucent faces again and re-uploads their indices
+    fn resort_translucent(&mut self, display: &glium::Display, chunk_pos: ChunkPosition, camera_pos: glam::Vec3) {
+        if self.translucent_mesh.indices.is_empty() { return; }
+        self.translucent_mesh.sort_back_to_front(camera_pos - (chunk_pos * chunk::CHUNK_SIZE).as_vec3());
+        self.layers[2].1 = glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &self.translucent_mesh.indices).unwrap();
+    }
+}
+
+// Chunk meshes are uploaded as GpuChunkVertex, which might be packed
+fn upload_chunk_mesh(display: &glium::Display, mesh: &ChunkMesh) -> (glium::VertexBuffer<GpuChunkVertex>, glium::IndexBuffer<u32>) {
+    (glium::VertexBuffer::new(display, &packed_vertex::to_gpu_vertices(&mesh.vertices)).unwrap(),
+     glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &mesh.indices).unwrap())
+}
+fn upload_mesh(display: &glium::Display, mesh: &ChunkMesh) -> (glium::VertexBuffer<ChunkVertex>, glium::IndexBuffer<u32>) {
+    (glium::VertexBuffer::new(display, &mesh.vertices).unwrap(),
+     glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &mesh.indices).unwrap())
+}
+
 fn main() {
     use glium::glutin;
 
@@ -27,48 +70,80 @@ fn main() {
 
     //display.gl_window().window().set_cursor_grab(CursorGrabMode::Confined);
 
-    let mut kb = window_context::Keyboard::new();
-    let mut m = window_context::Mouse::new();
+    // What each key does, players can change them in controls.cfg
+    let action_map = ActionMap::load_or_default("controls.cfg");
+
+    // --record <file> saves everything the player does, --replay <file> plays it back instead of using live input
+    let args: Vec<String> = std::env::args().collect();
+    let arg_after = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).cloned();
+    let record_path = arg_after("--record");
+    let mut replay = arg_after("--replay").map(|path| InputReplay::new(InputRecording::load(&path).expect("Couldn't load the recording!!")));
+    // Replays need the same world, so they bring their own seed
+    let seed = replay.as_ref().map_or_else(|| RandomNumberGenerator::new().next_u64(), |r| r.recording.seed);
+    let mut recorder = record_path.as_ref().map(|_| InputRecorder::new(seed));
 
     let mut cam = camera::FlyCamera::new();
+    let mut player = PlayerController::new();
     FlyCamera::reset_mouse_pos(&display);
 
-    let mut set_mode: u8 = 0;
     let mut draw_mode: u32 = 0;
     let mut colour_chunks: bool = true;
+    let mut show_debug = false;
     let mut polygon_mode = glium::PolygonMode::Fill;
     let mut cull_mode = glium::draw_parameters::BackfaceCullingMode::CullingDisabled;
 
     // Load images
     let mut images: Vec<glium::texture::RawImage2d<'_, u8>> = vec![];
 
-    let voxel_data_manager = VoxelDataManager::new(vec![
-        ("Air",         1, vec!["missing"]),
-        ("Grass Block", 0, vec!["grass_top", "dirt", "grass_side"]),
-        ("Dirt",        0, vec!["dirt"]),
-        ("Stone",       0, vec!["stone"]),
-        ("Deep Stone",  0, vec!["deep_stone"]),
-        ("Sand",        0, vec!["sand"]),
-        ("Oak Log",     0, vec!["oak_log_top", "oak_log_top", "oak_log_side"]),
-        ("Oak Planks",  0, vec!["oak_planks"]),
-        ("Leaves",      0, vec!["leaves"]),
-        ("Grass",       1, vec!["grass"]),
-        ("Cobblestone", 0, vec!["cobblestone"]),
-        ("Bricks",      0, vec!["bricks"]),
-        ("C4",          0, vec!["c4", "c4", "c4_side"]),
-    ], &mut images);
-    
-    let texture_2d_array = glium::texture::SrgbTexture2dArray::new(&display, images).unwrap();
+    let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut images);
+    block_behaviours::register_default_behaviours(&mut voxel_data_manager);
+    // The selection outline goes in the texture array after the voxel textures
+    let outline_texture_id = images.len() as u32;
+    // Mipmaps are made on the CPU rather than by the driver, so cutouts like leaves keep their shape in the distance
+    let mut mip_chains = voxel_data_manager.build_mipmaps();
+    {
+        let outline_image = image::open("res/outline.png").unwrap().to_rgba8();
+        images.push(glium::texture::RawImage2d::from_raw_rgba_reversed(outline_image.as_raw(), outline_image.dimensions()));
+        mip_chains.push(textures::build_mip_chain(&outline_image, Some(textures::ALPHA_CUTOFF)));
+    }
+
+    let mip_levels = mip_chains.iter().map(|levels| levels.len() as u32).min().unwrap_or(1);
+    let texture_2d_array = glium::texture::SrgbTexture2dArray::with_mipmaps(&display, images, glium::texture::MipmapsOption::EmptyMipmapsMax(mip_levels - 1)).unwrap();
+    textures::write_array_mipmaps(&display, &texture_2d_array, &mip_chains);
+    // Text has a texture of its own
+    let font_texture = {
+        use image::GenericImageView;
+        let font_image = image::open("res/font.png").unwrap();
+        let raw = glium::texture::RawImage2d::from_raw_rgba_reversed(&font_image.to_rgba8().into_raw(), font_image.dimensions());
+        glium::texture::SrgbTexture2d::new(&display, raw).unwrap()
+    };
+
+    // Icons for the hotbar, drawn from the voxel textures once and kept in cache/ until they change
+    let icon_atlas = IconAtlas::load_or_build("cache", &voxel_data_manager, block_icons::ICON_SIZE);
+    let icon_texture = {
+        let raw = glium::texture::RawImage2d::from_raw_rgba_reversed(icon_atlas.image.as_raw(), icon_atlas.image.dimensions());
+        glium::texture::SrgbTexture2d::new(&display, raw).unwrap()
+    };
+
+    let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, seed);
 
-    let mut chunk_manager = ChunkManager::new(voxel_data_manager);
+    // Everything that can be placed, and the blocks the player has to hand (kept between sessions)
+    let palette = inventory::build_palette(&chunk_manager.voxel_data_manager);
+    let mut hotbar = Hotbar::load_or_default("hotbar.cfg", &chunk_manager.voxel_data_manager);
+    // Input, movement and editing the world, everything that happens each frame that doesn't need the window
+    let mut logic = GameLogic::new(action_map, palette, display.gl_window().window().inner_size(), cam.camera.position);
 
-    let mut chunk_info: HashMap<ChunkPosition, (glium::VertexBuffer<ChunkVertex>, glium::IndexBuffer<u32>, u32)> = HashMap::new();
+    let mut chunk_info: HashMap<ChunkPosition, ChunkRenderData> = HashMap::new();
+    // Frame times and how long generating and meshing chunks takes, for the debug overlay
+    let mut metrics = Metrics::new();
+    // Chunks further away are drawn with less detail
+    let lod_policy = LodPolicy::default();
 
     for xi in -5..5 {
         for yi in -5..3 {
             for zi in -5..5 {
                 let chunk_pos: ChunkPosition = glam::ivec3(xi, yi, zi);
-                chunk_manager.add_chunk(chunk_pos);
+                metrics.time("Generation", || chunk_manager.add_chunk(chunk_pos));
     } } }
     
     let mut q = 0;
@@ -79,139 +154,143 @@ fn main() {
                 
                 //let c = chunk_manager.get_chunk_mut(chunk_pos).unwrap();
 
-                let chunk_mesh: chunk_mesh::ChunkMesh = chunk_mesh::ChunkMeshBuilder::build_chunk_mesh(chunk_pos, &mut chunk_manager);
+                let lod = lod_policy.select_lod(chunk_pos, cam.camera.position);
+                let coarser_neighbours = lod_policy.coarser_neighbours(chunk_pos, cam.camera.position);
+                let chunk_meshes = metrics.time("Meshing", || chunk_mesh::ChunkMeshBuilder::build_lod_chunk_mesh(chunk_pos, &mut chunk_manager, lod, coarser_neighbours));
                 
-                let vertex_buffer = glium::VertexBuffer::new(&display, &chunk_mesh.vertices).unwrap();
-                let indexs = glium::IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList,
-                    &chunk_mesh.indices).unwrap();
-                chunk_info.insert(chunk_pos, (vertex_buffer, indexs, q));
+                chunk_info.insert(chunk_pos, ChunkRenderData::new(&display, chunk_meshes, chunk_pos, cam.camera.position, lod, coarser_neighbours, q));
                 q+=1;
             }
         }
         q+=2;
     }
+    // Every chunk was just built, so nothing's out of date yet
+    chunk_manager.drain_dirty();
 
     let vertex_shader_src = include_str!("default.vert");
     let fragment_shader_src = include_str!("default.frag");
 
     let program = glium::Program::from_source(&display, vertex_shader_src, fragment_shader_src, None).unwrap();
+    // Chunks get their own program, since their vertices might be packed
+    let chunk_program = glium::Program::from_source(&display, packed_vertex::CHUNK_VERTEX_SHADER, fragment_shader_src, None).unwrap();
+    let ui_program = glium::Program::from_source(&display, include_str!("ui.vert"), include_str!("ui.frag"), None).unwrap();
 
-    let mut looking = false;
+    let mut last_sort_voxel = cam.camera.position.floor().as_ivec3();
+    let mut last_lod_chunk = Convert::global_to_chunk(last_sort_voxel);
     let mut fullscreen = false;
 
-    let mut deltatime = 0.0;
-    let mut deltatimer = SystemTime::now();
+    let mut clock = Clock::new();
+    // Live input's collected here until it's used, starting with the window size for mouse-look
+    let mut live_input = vec![InputEvent::Resized(display.gl_window().window().inner_size())];
     event_loop.run(move |ev, _, control_flow| {
-        deltatime = deltatimer.elapsed().unwrap().as_secs_f32();
-        deltatimer = SystemTime::now();
         let next_frame_time = std::time::Instant::now() +
             std::time::Duration::from_nanos(16_666_667);
         *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
         // -- LOGIC -- //
         match ev {
-            glutin::event::Event::WindowEvent { event, .. } => match event {
-                glutin::event::WindowEvent::CloseRequested => 
-                    *control_flow = glutin::event_loop::ControlFlow::Exit,
-                glutin::event::WindowEvent::KeyboardInput { device_id, input, is_synthetic } => 
-                    {kb.process_input(input);},
-                glutin::event::WindowEvent::MouseInput { device_id, state, button, modifiers } => {
-                    m.process_input(state, button);
+            glutin::event::Event::WindowEvent { event, .. } => {
+                match event {
+                    glutin::event::WindowEvent::CloseRequested => 
+                        *control_flow = glutin::event_loop::ControlFlow::Exit,
+                    // Live input is ignored while replaying
+                    glutin::event::WindowEvent::KeyboardInput { .. } | glutin::event::WindowEvent::MouseInput { .. } |
+                    glutin::event::WindowEvent::MouseWheel { .. } | glutin::event::WindowEvent::CursorMoved { .. } |
+                    glutin::event::WindowEvent::Resized(_) if replay.is_some() => return,
+                    glutin::event::WindowEvent::KeyboardInput { device_id, input, is_synthetic } => 
+                        {
+                            if let Some(key) = input.virtual_keycode { live_input.push(InputEvent::Key(key, input.state)); }
+                        },
+                    glutin::event::WindowEvent::MouseInput { device_id, state, button, modifiers } => {
+                        live_input.push(InputEvent::Button(button, state));
+                    }
+                    glutin::event::WindowEvent::MouseWheel { device_id, delta, phase, modifiers } => 
+                        {
+                            live_input.push(InputEvent::Scroll(delta));
+                        },
+                    glutin::event::WindowEvent::CursorMoved { device_id, position, modifiers } => 
+                        {
+                            live_input.push(InputEvent::CursorMoved(position));
+                            // The camera turns once the input's played, but the cursor has to go back to the middle
+                            // straight away so the next move is measured from there
+                            if logic.looking { FlyCamera::reset_mouse_pos(&display); }
+                        },
+                    glutin::event::WindowEvent::Resized(size) => live_input.push(InputEvent::Resized(size)),
+                    _ => (),
                 }
-                glutin::event::WindowEvent::MouseWheel { device_id, delta, phase, modifiers } => 
-                    m.set_scroll_delta(delta),
-                glutin::event::WindowEvent::CursorMoved { device_id, position, modifiers } => 
-                    { m.set_pos(position); if (looking) { cam.handle_mouse_looking(&display, &position); }
-                    },
-                _ => return,
+                // Input's only collected here, it gets used once the frame's events are done
+                return;
             },
-            glutin::event::Event::MainEventsCleared => {
-                // Rendering
-                let mut target = display.draw();
-
-                cam.camera.calculate_perspective_matrix(&target);
-                cam.camera.calculate_view_matrix();
-        
-                // Clear the screen
-                target.clear_color_and_depth((0.05078125, 0.0546875, 0.0859375, 1.0), 1.0);
-                // Draw the triangle
-        
-                for (pos, (vertex_buffer, index_buffer, q)) in &chunk_info {
-                    let uniforms = uniform! {
-                        matrix: [
-                            [1.0, 0.0, 0.0, 0.0],
-                            [0.0, 1.0, 0.0, 0.0],
-                            [0.0, 0.0, 1.0, 0.0],
-                            [0.0, 0.0, 0.0, 1.0f32],
-                        ],
-                        perspective: cam.camera.perspective_matrix.to_cols_array_2d(),
-                        view: cam.camera.view_matrix.to_cols_array_2d(),
-                        texture_array: texture_2d_array.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
-                        // chunk_position: glam::ivec3(0, 0, 0).to_array(),
-                        chunk_position: pos.to_array(),
-                        chunk_colour: *q,
-                        draw_mode: draw_mode,
-                        colour_chunks: colour_chunks,
-                    };
-            
-                    target.draw(vertex_buffer, index_buffer, &program, &uniforms, &glium::DrawParameters {
-                        polygon_mode: polygon_mode,
-                        depth: glium::Depth {
-                            
-                            test: glium::draw_parameters::DepthTest::IfLess,
-                            write: true,
-                            .. Default::default()
-                        },
-                        blend: Blend::alpha_blending(),
-                        backface_culling: cull_mode,
-                        .. Default::default()
-                    }).unwrap();  
+            // Everything below runs once a frame
+            glutin::event::Event::MainEventsCleared => (),
+            glutin::event::Event::LoopDestroyed => {
+                if let (Some(recorder), Some(path)) = (&recorder, &record_path) {
+                    match recorder.recording.save(path) {
+                        Ok(()) => println!("Saved {} frames of input to {}", recorder.recording.frames.len(), path),
+                        Err(e) => println!("Couldn't save the recording to {}!! {}", path, e),
+                    }
                 }
-                
-                target.finish().unwrap();
+                if let Err(e) = hotbar.save("hotbar.cfg", &chunk_manager.voxel_data_manager) {
+                    println!("Couldn't save the hotbar!! {}", e);
+                }
+                return;
             },
-            _ => (),
+            _ => return,
 
-            // TODO: make transparent objects their own mesh
         }
 
-        if kb.key_pressed(glutin::event::VirtualKeyCode::Escape) {
+        // Use the recorded input and frame time while replaying, going back to live input once the recording ends
+        let mut live_frame = InputFrame { deltatime: clock.tick(), events: std::mem::take(&mut live_input) };
+        let frame = match replay.as_mut().map(|playing| playing.next_frame().cloned()) {
+            Some(Some(frame)) => frame,
+            Some(None) => {
+                println!("Replay finished");
+                replay = None;
+                // The recording's window size was used until now, so go back to the real one
+                live_frame.events.insert(0, InputEvent::Resized(display.gl_window().window().inner_size()));
+                live_frame
+            }
+            None => live_frame,
+        };
+        if let Some(recorder) = &mut recorder { recorder.record_frame(&frame); }
+        metrics.record_frame(frame.deltatime);
+
+        let was_looking = logic.looking;
+        let actions = logic.step_frame(&mut chunk_manager, &mut cam, &mut player, &mut hotbar, &frame);
+        // The chunks explosions changed get rebuilt from drain_dirty, so their results are just cleared out
+        chunk_manager.drain_explosions();
+
+        if actions.action_pressed(Action::Quit) {
             *control_flow = glutin::event_loop::ControlFlow::Exit;
         }
-        if kb.key_pressed(glutin::event::VirtualKeyCode::C) {
-            println!("Wireframe toggled");
+        if actions.action_pressed(Action::ToggleWireframe) {
             polygon_mode = if matches!(polygon_mode, glium::PolygonMode::Line) {glium::PolygonMode::Fill} else {glium::PolygonMode::Line};
         }
-        if kb.key_pressed(glutin::event::VirtualKeyCode::B) {
-            println!("Backface Culling toggled");
+        if actions.action_pressed(Action::ToggleBackfaceCulling) {
             cull_mode = if matches!(cull_mode, glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise) {glium::draw_parameters::BackfaceCullingMode::CullingDisabled} else {glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise};
         }
-        if kb.key_pressed(glutin::event::VirtualKeyCode::E) {
-            looking = !looking;
+        // Mouse-look was toggled, or turned off by orbiting
+        if logic.looking != was_looking {
             // Reset mouse to prevent jump (TODO: Make work)
-            if looking == true {
+            if logic.looking {
                 FlyCamera::reset_mouse_pos(&display);
                 display.gl_window().window().set_cursor_visible(false);
             } else {
                 display.gl_window().window().set_cursor_visible(true);
             }
         }
-        if kb.key_pressed(glutin::event::VirtualKeyCode::X) {
+        if actions.action_pressed(Action::ToggleChunkColours) {
             colour_chunks = !colour_chunks;
         }
-        if kb.key_pressed(glutin::event::VirtualKeyCode::Z) {
+        if actions.action_pressed(Action::ToggleDebugOverlay) {
+            show_debug = !show_debug;
+        }
+        if actions.action_pressed(Action::CycleDrawMode) {
             draw_mode += 1;
             if draw_mode > 2 {
                 draw_mode = 0;
             }
         }
-        if kb.key_pressed(glutin::event::VirtualKeyCode::Q) {
-            set_mode += 1;
-            if set_mode > 11 {
-                set_mode = 0;
-            }
-            println!("Block: {:?}", chunk_manager.voxel_data_manager.get_name(set_mode));
-        }
-        if kb.key_held(glutin::event::VirtualKeyCode::LAlt) && kb.key_pressed(glutin::event::VirtualKeyCode::Return) {
+        if actions.action_pressed(Action::ToggleFullscreen) {
             if fullscreen {
                 display.gl_window().window().set_fullscreen(None);
                 fullscreen = false;
@@ -225,46 +304,217 @@ fn main() {
             FlyCamera::reset_mouse_pos(&display);
         }
 
-        cam.handle_movement(&kb, &deltatime);
-        
-        let mut to_rebuild: Vec<ChunkPosition> = vec![];
-        
-        // TODO: AO needs updating
-        for i in 0..27 {
-            let cam_head_pos = cam.camera.position.as_ivec3() + glam::ivec3(i % 3, (i / 9)-1, (i / 3)%3)-(3/2);
-            if chunk_manager.get_voxel(cam_head_pos) == Some(set_mode) {
-                continue;
-            }
-            match chunk_manager.set_voxel(cam_head_pos, set_mode) {
-                true => {
-                    let cam_head_pos_chunk = Convert::global_to_chunk(cam_head_pos);
-                    to_rebuild.push(cam_head_pos_chunk);
-                    for (j, vp, cp) in chunk::RELATIVE_NEIGHBOURS {
-                        if Convert::global_to_local(cam_head_pos).to_array()[j] == vp {
-                            let chunk_neigbour = cam_head_pos_chunk + cp;
-                            if chunk_manager.get_chunk(chunk_neigbour).is_some() {
-                                to_rebuild.push(chunk_neigbour);
-                            }
-                        }
-                    }
-                }
-                _ => {}
-            }
+        // Rebuild chunks that have changed, along with any that need a different level of detail now the camera's moved
+        // Chunks next to one whose level of detail changed are rebuilt too, since their borders depend on it
+        let mut rebuild = chunk_manager.drain_dirty();
+        let camera_chunk = Convert::global_to_chunk(cam.camera.position.floor().as_ivec3());
+        if camera_chunk != last_lod_chunk {
+            last_lod_chunk = camera_chunk;
+            rebuild.extend(chunk_info.iter()
+                .filter(|(cp, info)| lod_policy.select_lod(**cp, cam.camera.position) != info.lod
+                    || lod_policy.coarser_neighbours(**cp, cam.camera.position) != info.coarser_neighbours)
+                .map(|(cp, _)| *cp));
+        }
+        for cp in rebuild {
+            let Some(info) = chunk_info.get_mut(&cp) else { continue; };
+            let lod = lod_policy.select_lod(cp, cam.camera.position);
+            let coarser_neighbours = lod_policy.coarser_neighbours(cp, cam.camera.position);
+            let new_chunk_meshes = metrics.time("Meshing", || chunk_mesh::ChunkMeshBuilder::build_lod_chunk_mesh(cp, &mut chunk_manager, lod, coarser_neighbours));
+            *info = ChunkRenderData::new(&display, new_chunk_meshes, cp, cam.camera.position, lod, coarser_neighbours, info.colour);
         }
 
-        to_rebuild.dedup();
-        
-        for cp in to_rebuild.clone() {
-            let new_chunk_mesh = chunk_mesh::ChunkMeshBuilder::build_chunk_mesh(cp, &mut chunk_manager);
-            chunk_info.get_mut(&cp).unwrap().0 = glium::VertexBuffer::new(&display, &new_chunk_mesh.vertices).unwrap();
-            chunk_info.get_mut(&cp).unwrap().1 = glium::IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList,
-                    &new_chunk_mesh.indices).unwrap();
-                        
+        // Only re-sort translucent faces once the camera has moved into another voxel, doing it every frame is a waste
+        let camera_voxel = cam.camera.position.floor().as_ivec3();
+        if camera_voxel != last_sort_voxel {
+            last_sort_voxel = camera_voxel;
+            for (cp, info) in chunk_info.iter_mut() {
+                info.resort_translucent(&display, *cp, cam.camera.position);
+            }
         }
         
 
         // -- END LOGIC -- //
-        kb.clear();
-        m.clear();
+
+        // Draw the camera part way between the last two steps, so movement's smooth at any framerate
+        let mut render_camera = cam.camera.clone();
+        render_camera.position = logic.previous_camera_position.lerp(cam.camera.position, logic.simulation.alpha());
+        let mut target = display.draw();
+
+        render_camera.calculate_perspective_matrix(&target);
+        render_camera.calculate_view_matrix();
+
+        // Clear the screen
+        target.clear_color_and_depth((0.05078125, 0.0546875, 0.0859375, 1.0), 1.0);
+        // Draw the triangle
+
+        // Opaque and cutout faces first, then the translucent ones from the furthest chunk to the closest
+        let camera_pos = render_camera.position;
+        let chunk_distance = |pos: &ChunkPosition| ((*pos * chunk::CHUNK_SIZE).as_vec3() + (chunk::CHUNK_SIZE / 2) as f32).distance_squared(camera_pos);
+        // Chunks that are off screen, or hidden behind solid ground, aren't drawn at all
+        let camera_chunk = Convert::global_to_chunk(camera_pos.floor().as_ivec3());
+        let reachable_chunks = chunk_visibility::find_visible_chunks(camera_chunk, |p| chunk_info.get(&p).map(|info| &info.visibility));
+        let visible_chunks = render_camera.get_frustum().cull_chunks(chunk_info.keys()
+            .filter(|p| reachable_chunks.as_ref().is_none_or(|r| r.contains(p))));
+        let mut translucent_order = visible_chunks.clone();
+        translucent_order.sort_by(|a, b| chunk_distance(b).total_cmp(&chunk_distance(a)));
+        let draw_order = visible_chunks.iter().flat_map(|pos| [(pos, &chunk_info[pos], 0), (pos, &chunk_info[pos], 1)])
+            .chain(translucent_order.iter().map(|pos| (pos, &chunk_info[pos], 2)));
+
+        let falling_blocks_mesh = chunk_mesh::ChunkMeshBuilder::build_falling_blocks_mesh(&chunk_manager.falling_blocks, &chunk_manager.voxel_data_manager);
+        let falling_blocks_data = (!falling_blocks_mesh.indices.is_empty()).then(|| upload_mesh(&display, &falling_blocks_mesh));
+
+        // Falling blocks are already in world space, and drawn first since they're opaque
+        if let Some((vertex_buffer, index_buffer)) = &falling_blocks_data {
+            let uniforms = uniform! {
+                matrix: glam::Mat4::IDENTITY.to_cols_array_2d(),
+                perspective: render_camera.perspective_matrix.to_cols_array_2d(),
+                view: render_camera.view_matrix.to_cols_array_2d(),
+                texture_array: texture_2d_array.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear),
+                chunk_position: [0, 0, 0],
+                chunk_colour: 4u32,
+                draw_mode: draw_mode,
+                colour_chunks: false,
+                alpha_cutoff: textures::ALPHA_CUTOFF,
+                opacity: 1.0f32,
+            };
+            target.draw(vertex_buffer, index_buffer, &program, &uniforms, &glium::DrawParameters {
+                polygon_mode,
+                depth: glium::Depth {
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    write: true,
+                    .. Default::default()
+                },
+                blend: Blend::alpha_blending(),
+                backface_culling: cull_mode,
+                .. Default::default()
+            }).unwrap();
+        }
+
+        for (pos, info, layer) in draw_order {
+            let (vertex_buffer, index_buffer) = &info.layers[layer];
+            if index_buffer.len() == 0 { continue; }
+            let translucent = layer == 2;
+            let uniforms = uniform! {
+                matrix: [
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [0.0, 0.0, 0.0, 1.0f32],
+                ],
+                perspective: render_camera.perspective_matrix.to_cols_array_2d(),
+                view: render_camera.view_matrix.to_cols_array_2d(),
+                texture_array: texture_2d_array.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear),
+                // chunk_position: glam::ivec3(0, 0, 0).to_array(),
+                chunk_position: pos.to_array(),
+                chunk_colour: info.colour,
+                draw_mode: draw_mode,
+                colour_chunks: colour_chunks,
+                // Cutout pixels are either there or not, translucent ones are only thrown away if they're fully transparent
+                alpha_cutoff: if translucent { 0.0 } else { textures::ALPHA_CUTOFF },
+                opacity: 1.0f32,
+            };
+    
+            target.draw(vertex_buffer, index_buffer, &chunk_program, &uniforms, &glium::DrawParameters {
+                polygon_mode: polygon_mode,
+                depth: glium::Depth {
+                    
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    // Translucent faces shouldn't hide the ones behind them
+                    write: !translucent,
+                    .. Default::default()
+                },
+                blend: Blend::alpha_blending(),
+                backface_culling: cull_mode,
+                .. Default::default()
+            }).unwrap();  
+        }
+
+        // Outline what's being looked at, and show a see-through copy of the selected block where it would be placed
+        let is_target = |v| v != 0 && chunk_manager.get_fluid(v).is_none();
+        let looked_at = logic.orbit.is_none().then(|| chunk_manager.raycast(render_camera.position, render_camera.forward(), REACH, is_target)).flatten();
+        if let Some(hit) = looked_at {
+            let voxel_data_manager = &chunk_manager.voxel_data_manager;
+            let voxel_id = chunk_manager.get_voxel(hit.voxel).unwrap_or(0);
+            let outline_mesh = block_highlight::build_outline_mesh(hit.voxel, voxel_id, voxel_data_manager, outline_texture_id);
+            let ghost_mesh = match hotbar.selected_voxel() {
+                Some(voxel_id) if game_logic::can_place_at(&chunk_manager, hit.voxel + hit.normal) =>
+                    block_highlight::build_ghost_mesh(hit.voxel + hit.normal, voxel_id, voxel_data_manager),
+                _ => ChunkMesh::new(),
+            };
+            // The outline's drawn right on top of the block's faces, so it has to pass the depth test where they're equal
+            for (mesh, opacity, depth_test) in [
+                (outline_mesh, 1.0f32, glium::draw_parameters::DepthTest::IfLessOrEqual),
+                (ghost_mesh, block_highlight::GHOST_OPACITY, glium::draw_parameters::DepthTest::IfLess),
+            ] {
+                if mesh.indices.is_empty() { continue; }
+                let (vertex_buffer, index_buffer) = upload_mesh(&display, &mesh);
+                let uniforms = uniform! {
+                    matrix: glam::Mat4::IDENTITY.to_cols_array_2d(),
+                    perspective: render_camera.perspective_matrix.to_cols_array_2d(),
+                    view: render_camera.view_matrix.to_cols_array_2d(),
+                    texture_array: texture_2d_array.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::LinearMipmapLinear),
+                    chunk_position: [0, 0, 0],
+                    chunk_colour: 4u32,
+                    draw_mode: 0u32,
+                    colour_chunks: false,
+                    alpha_cutoff: textures::ALPHA_CUTOFF,
+                    opacity: opacity,
+                };
+                target.draw(&vertex_buffer, &index_buffer, &program, &uniforms, &glium::DrawParameters {
+                    depth: glium::Depth {
+                        test: depth_test,
+                        write: false,
+                        .. Default::default()
+                    },
+                    blend: Blend::alpha_blending(),
+                    .. Default::default()
+                }).unwrap();
+            }
+        }
+
+        // The UI goes over everything
+        let (width, height) = target.get_dimensions();
+        let mut ui = Ui::new(glam::vec2(width as f32, height as f32));
+        if logic.orbit.is_none() {
+            ui::crosshair(&mut ui);
+        }
+        ui::hotbar(&mut ui, &hotbar, &chunk_manager.voxel_data_manager, &icon_atlas);
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        let status_lines = vec![
+            format!("Mode: {}", if logic.orbit.is_some() { "Orbit".to_string() } else { format!("{:?}", player.mode) }),
+            format!("View: {}", ["Textured", "Lighting", "AO"][draw_mode as usize]),
+            format!("Wireframe: {}", on_off(matches!(polygon_mode, glium::PolygonMode::Line))),
+            format!("Backface culling: {}", on_off(matches!(cull_mode, glium::draw_parameters::BackfaceCullingMode::CullCounterClockwise))),
+            format!("Chunk colours: {}", on_off(colour_chunks)),
+        ];
+        let status_panel = ui::text_panel(&mut ui, glam::Vec2::splat(ui::HOTBAR_MARGIN), &status_lines);
+        if show_debug {
+            let debug_info = ui::DebugInfo {
+                camera: &render_camera,
+                target: looked_at.map(|hit| (hit.voxel, chunk_manager.voxel_data_manager.get_name(chunk_manager.get_voxel(hit.voxel).unwrap_or(0)))),
+                loaded_chunks: chunk_manager.chunks.len(),
+                meshed_chunks: chunk_info.len(),
+                drawn_chunks: visible_chunks.len(),
+                vertices: chunk_info.values().flat_map(|info| info.layers.iter()).map(|(v, _)| v.len()).sum(),
+                indices: chunk_info.values().flat_map(|info| info.layers.iter()).map(|(_, i)| i.len()).sum(),
+            };
+            ui::debug_overlay(&mut ui, glam::vec2(ui::HOTBAR_MARGIN, status_panel.max().y + ui::HOTBAR_MARGIN), &debug_info, &metrics);
+        }
+        if !ui.indices.is_empty() {
+            let vertex_buffer = glium::VertexBuffer::new(&display, &ui.vertices).unwrap();
+            let index_buffer = glium::IndexBuffer::new(&display, glium::index::PrimitiveType::TrianglesList, &ui.indices).unwrap();
+            let uniforms = uniform! {
+                screen_size: ui.screen_size.to_array(),
+                font: font_texture.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+                icons: icon_texture.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+                texture_array: texture_2d_array.sampled().magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest).minify_filter(glium::uniforms::MinifySamplerFilter::Nearest),
+            };
+            target.draw(&vertex_buffer, &index_buffer, &ui_program, &uniforms, &glium::DrawParameters {
+                blend: Blend::alpha_blending(),
+                .. Default::default()
+            }).unwrap();
+        }
+        
+        target.finish().unwrap();
     });
 }
@@ -0,0 +1,150 @@
+use std::{collections::{BTreeMap, VecDeque}, time::{Duration, Instant}};
+
+// How many frames of frame times are kept, for the graph
+pub const FRAME_HISTORY: usize = 240;
+
+// How long one kind of work (e.g. meshing a chunk) has been taking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingStats {
+    pub count: u64,
+    pub total: Duration,
+    pub last: Duration,
+    pub max: Duration,
+}
+
+impl TimingStats {
+    pub fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.last = duration;
+        self.max = self.max.max(duration);
+    }
+    pub fn average(&self) -> Duration {
+        if self.count == 0 { Duration::ZERO } else { Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64) }
+    }
+}
+
+// Collects frame times and how long named bits of work take. Nothing here needs a window, so headless runs
+// (e.g. replaying a recording) can use it to measure things too
+pub struct Metrics {
+    frame_times: VecDeque<f32>,
+    // Sorted by name so they're always listed in the same order
+    timings: BTreeMap<&'static str, TimingStats>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self { frame_times: VecDeque::with_capacity(FRAME_HISTORY), timings: BTreeMap::new() }
+    }
+
+    // Adds a frame time in seconds, forgetting the oldest once there are FRAME_HISTORY of them
+    pub fn record_frame(&mut self, frame_time: f32) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+    // Oldest first
+    pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frame_times.iter().copied()
+    }
+    pub fn average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() { return 0.0; }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+    pub fn max_frame_time(&self) -> f32 {
+        self.frame_times.iter().copied().fold(0.0, f32::max)
+    }
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+
+    pub fn record(&mut self, name: &'static str, duration: Duration) {
+        self.timings.entry(name).or_default().add(duration);
+    }
+    // Runs f and records how long it took under name
+    pub fn time<R>(&mut self, name: &'static str, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.record(name, start.elapsed());
+        result
+    }
+    pub fn get_timing(&self, name: &str) -> Option<&TimingStats> {
+        self.timings.get(name)
+    }
+    pub fn timings(&self) -> impl Iterator<Item = (&'static str, &TimingStats)> + '_ {
+        self.timings.iter().map(|(name, stats)| (*name, stats))
+    }
+    pub fn reset_timings(&mut self) {
+        self.timings.clear();
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_latest_frames_are_kept() {
+        let mut metrics = Metrics::new();
+        for i in 0..FRAME_HISTORY + 10 {
+            metrics.record_frame(i as f32);
+        }
+        let frame_times: Vec<f32> = metrics.frame_times().collect();
+        assert_eq!(frame_times.len(), FRAME_HISTORY);
+        assert_eq!(frame_times[0], 10.0);
+        assert_eq!(*frame_times.last().unwrap(), (FRAME_HISTORY + 9) as f32);
+        assert_eq!(metrics.max_frame_time(), (FRAME_HISTORY + 9) as f32);
+    }
+
+    #[test]
+    fn frame_stats_with_no_frames_are_zero() {
+        let metrics = Metrics::new();
+        assert_eq!(metrics.average_frame_time(), 0.0);
+        assert_eq!(metrics.max_frame_time(), 0.0);
+        assert_eq!(metrics.fps(), 0.0);
+    }
+
+    #[test]
+    fn fps_comes_from_the_average_frame_time() {
+        let mut metrics = Metrics::new();
+        metrics.record_frame(0.015);
+        metrics.record_frame(0.025);
+        assert!((metrics.average_frame_time() - 0.02).abs() < 1e-6);
+        assert!((metrics.fps() - 50.0).abs() < 1e-3);
+        assert_eq!(metrics.max_frame_time(), 0.025);
+    }
+
+    #[test]
+    fn timing_stats_track_the_last_max_and_average() {
+        let mut stats = TimingStats::default();
+        assert_eq!(stats.average(), Duration::ZERO);
+        for millis in [4, 10, 1] {
+            stats.add(Duration::from_millis(millis));
+        }
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.last, Duration::from_millis(1));
+        assert_eq!(stats.max, Duration::from_millis(10));
+        assert_eq!(stats.average(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn timings_are_recorded_by_name() {
+        let mut metrics = Metrics::new();
+        assert_eq!(metrics.time("Meshing", || 7), 7);
+        metrics.record("Meshing", Duration::from_millis(2));
+        metrics.record("Generation", Duration::from_millis(3));
+        assert_eq!(metrics.get_timing("Meshing").unwrap().count, 2);
+        // Listed by name, whatever order they were recorded in
+        assert_eq!(metrics.timings().map(|(name, _)| name).collect::<Vec<_>>(), ["Generation", "Meshing"]);
+        metrics.reset_timings();
+        assert_eq!(metrics.get_timing("Meshing"), None);
+    }
+}
@@ -0,0 +1,51 @@
+#version 140
+
+// Everything's packed into two uints, see PackedChunkVertex
+in uvec2 data;
+
+out vec2 v_tex_coords;
+out float v_light_level;
+out vec3 v_chunk_colour;
+out float v_texture_id;
+out float v_ambient_occlusion;
+
+uniform ivec3 chunk_position;
+uniform uint chunk_colour;
+uniform bool colour_chunks;
+uniform mat4 perspective;
+uniform mat4 view;
+uniform mat4 matrix;
+
+vec2 tex_coords_array[4] = vec2[4](
+	vec2(0, 1),
+	vec2(1, 1),
+	vec2(1, 0),
+	vec2(0, 0)
+);
+
+vec3 chunk_colour_array[5] = vec3[5](
+	vec3(0.8, 0.3, 0.3),
+	vec3(0.8, 0.8, 0.3),
+	vec3(0.3, 0.8, 0.3),
+	vec3(0.5, 0.8, 1.0),
+	vec3(1.0, 1.0, 1.0)
+);
+
+void main() {
+	vec3 position = vec3(data.x & 1023u, (data.x >> 10) & 1023u, (data.x >> 20) & 1023u) / 16.0;
+	uint tex_coords = data.x >> 30;
+	float ambient_occlusion = float(data.y & 3u);
+	uint light_level = (data.y >> 2) & 7u;
+	uint texture_id = (data.y >> 5) & 65535u;
+
+	v_texture_id = float(texture_id);
+	v_ambient_occlusion = 0.2*(1-(ambient_occlusion/3));
+    v_tex_coords = tex_coords_array[tex_coords];
+	if (colour_chunks) {
+		v_chunk_colour = chunk_colour_array[(chunk_colour%5u)];
+	} else {
+		v_chunk_colour = vec3(1.0, 1.0, 1.0);
+	}
+    v_light_level = float(light_level) / 5;
+    gl_Position = perspective * (view * matrix) * vec4((position + (chunk_position * 32)), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,137 @@
+use glium::implement_vertex;
+implement_vertex!(PackedChunkVertex, data);
+
+use crate::chunk_mesh::ChunkVertex;
+
+// Positions are stored in 16ths of a voxel, which is enough for fluid surfaces and crops to look right
+pub const POSITION_PRECISION: f32 = 16.0;
+// The biggest position that can be stored on each axis (10 bits)
+pub const MAX_POSITION: f32 = 1023.0 / POSITION_PRECISION;
+pub const MAX_TEXTURE_ID: u32 = 0xFFFF;
+
+// A ChunkVertex squashed into two u32s, decoded again in packed.vert
+// data[0]: x (10 bits) | y (10) | z (10) | tex_coords (2)
+// data[1]: ambient_occlusion (2 bits) | light_level (3) | texture_id (16)
+// Positions have to be between 0 and MAX_POSITION, so this only works for meshes relative to their chunk
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PackedChunkVertex {
+    pub data: [u32; 2],
+}
+
+impl PackedChunkVertex {
+    pub fn pack(vertex: &ChunkVertex) -> Self {
+        debug_assert!(vertex.position.iter().all(|&p| (0.0..=MAX_POSITION).contains(&p)), "Vertex position can't be packed!! {:?}", vertex.position);
+        debug_assert!(vertex.texture_id <= MAX_TEXTURE_ID, "Texture ID can't be packed!! {:?}", vertex.texture_id);
+        let [x, y, z] = vertex.position.map(|p| (p * POSITION_PRECISION).round().clamp(0.0, 1023.0) as u32);
+        let ao = (vertex.ambient_occlusion.round().clamp(0.0, 3.0)) as u32;
+        Self { data: [
+            x | y << 10 | z << 20 | (vertex.tex_coords as u32 & 3) << 30,
+            ao | (vertex.light_level as u32 & 7) << 2 | (vertex.texture_id & MAX_TEXTURE_ID) << 5,
+        ] }
+    }
+
+    pub fn unpack(&self) -> ChunkVertex {
+        let [a, b] = self.data;
+        let position = [a & 1023, (a >> 10) & 1023, (a >> 20) & 1023].map(|p| p as f32 / POSITION_PRECISION);
+        ChunkVertex {
+            position,
+            tex_coords: (a >> 30) as u8,
+            light_level: ((b >> 2) & 7) as u8,
+            texture_id: (b >> 5) & MAX_TEXTURE_ID,
+            ambient_occlusion: (b & 3) as f32,
+        }
+    }
+}
+
+// The vertex that chunk meshes are uploaded as, which one is picked with the packed-vertices feature
+#[cfg(feature = "packed-vertices")]
+pub type GpuChunkVertex = PackedChunkVertex;
+#[cfg(not(feature = "packed-vertices"))]
+pub type GpuChunkVertex = ChunkVertex;
+
+// The vertex shader that goes with GpuChunkVertex
+#[cfg(feature = "packed-vertices")]
+pub const CHUNK_VERTEX_SHADER: &str = include_str!("packed.vert");
+#[cfg(not(feature = "packed-vertices"))]
+pub const CHUNK_VERTEX_SHADER: &str = include_str!("default.vert");
+
+// Turns a mesh's vertices into the ones that get uploaded
+#[cfg(feature = "packed-vertices")]
+pub fn to_gpu_vertices(vertices: &[ChunkVertex]) -> Vec<GpuChunkVertex> {
+    vertices.iter().map(PackedChunkVertex::pack).collect()
+}
+#[cfg(not(feature = "packed-vertices"))]
+pub fn to_gpu_vertices(vertices: &[ChunkVertex]) -> Vec<GpuChunkVertex> {
+    vertices.to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3], tex_coords: u8, light_level: u8, texture_id: u32, ambient_occlusion: f32) -> ChunkVertex {
+        ChunkVertex { position, tex_coords, light_level, texture_id, ambient_occlusion }
+    }
+
+    fn assert_round_trips(v: ChunkVertex) {
+        let unpacked = PackedChunkVertex::pack(&v).unpack();
+        assert_eq!(unpacked.position, v.position);
+        assert_eq!(unpacked.tex_coords, v.tex_coords);
+        assert_eq!(unpacked.light_level, v.light_level);
+        assert_eq!(unpacked.texture_id, v.texture_id);
+        assert_eq!(unpacked.ambient_occlusion, v.ambient_occlusion);
+    }
+
+    // The same bit twiddling as packed.vert, so the layout's checked against what the shader does too
+    fn decode_like_shader(data: [u32; 2]) -> ([f32; 3], u32, f32, u32, u32) {
+        let position = [data[0] & 1023, (data[0] >> 10) & 1023, (data[0] >> 20) & 1023].map(|p| p as f32 / 16.0);
+        (position, data[0] >> 30, (data[1] & 3) as f32, (data[1] >> 2) & 7, (data[1] >> 5) & 65535)
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        // Every position in a chunk in 16ths of a voxel, with each axis at a different value so mixed up axes get caught
+        let steps = 32 * POSITION_PRECISION as u32;
+        for step in 0..=steps {
+            let p = |s: u32| s as f32 / POSITION_PRECISION;
+            assert_round_trips(vertex([p(step), p(steps - step), p((step * 7) % (steps + 1))], 3, 7, MAX_TEXTURE_ID, 3.0));
+        }
+        for y in 0..=steps {
+            for x in 0..=steps {
+                let position = [x as f32 / POSITION_PRECISION, y as f32 / POSITION_PRECISION, 0.5];
+                assert_round_trips(vertex(position, 0, 0, 0, 0.0));
+            }
+        }
+        // The very edge of what fits
+        assert_round_trips(vertex([MAX_POSITION; 3], 3, 7, MAX_TEXTURE_ID, 3.0));
+    }
+
+    #[test]
+    fn other_fields_round_trip() {
+        for tex_coords in 0..=3 {
+            for ao in 0..=3 {
+                for light_level in 0..=7 {
+                    for texture_id in 0..=MAX_TEXTURE_ID {
+                        assert_round_trips(vertex([32.0, 0.0, 16.5], tex_coords, light_level, texture_id, ao as f32));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shader_decodes_the_same() {
+        for v in [
+            vertex([0.0, 0.0, 0.0], 0, 0, 0, 0.0),
+            vertex([32.0, 31.9375, 0.0625], 1, 5, 17, 2.0),
+            vertex([MAX_POSITION; 3], 3, 7, MAX_TEXTURE_ID, 3.0),
+        ] {
+            let (position, tex_coords, ao, light_level, texture_id) = decode_like_shader(PackedChunkVertex::pack(&v).data);
+            assert_eq!(position, v.position);
+            assert_eq!(tex_coords, v.tex_coords as u32);
+            assert_eq!(ao, v.ambient_occlusion);
+            assert_eq!(light_level, v.light_level as u32);
+            assert_eq!(texture_id, v.texture_id);
+        }
+    }
+}
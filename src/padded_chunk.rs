@@ -0,0 +1,92 @@
+use crate::{chunk::{Chunk, ChunkPosition, VoxelPosition, VoxelID, VoxelState, CHUNK_SIZE, CHUNK_SIZE_MIN1}, chunk_manager::ChunkManager};
+
+pub const PADDED_SIZE: i32 = CHUNK_SIZE + 2;
+pub const PADDED_SIZE_USIZE: usize = PADDED_SIZE as usize;
+pub const PADDED_VOLUME: usize = PADDED_SIZE_USIZE * PADDED_SIZE_USIZE * PADDED_SIZE_USIZE;
+
+// A copy of a chunk along with a one voxel border taken from all 26 of its neighbours (34x34x34)
+// Meshing only looks at this, so it never has to go through the chunk HashMap for every voxel
+// Voxels in neighbours that aren't loaded are treated as air
+pub struct PaddedChunk {
+    pub position: ChunkPosition,
+    voxels: Vec<VoxelID>,
+    states: Vec<VoxelState>,
+}
+
+impl PaddedChunk {
+    // Makes an empty padded chunk full of air
+    pub fn new(position: ChunkPosition) -> Self {
+        Self { position, voxels: vec![0; PADDED_VOLUME], states: vec![0; PADDED_VOLUME] }
+    }
+    // Copies a chunk and its border from the chunk manager, returns None if the chunk isn't loaded
+    pub fn from_chunk_manager(position: ChunkPosition, chunk_manager: &ChunkManager) -> Option<Self> {
+        chunk_manager.get_chunk(position)?;
+        let mut padded = PaddedChunk::new(position);
+        for offset_y in -1..=1 {
+            for offset_z in -1..=1 {
+                for offset_x in -1..=1 {
+                    let offset = glam::ivec3(offset_x, offset_y, offset_z);
+                    if let Some(chunk) = chunk_manager.get_chunk(position + offset) {
+                        padded.copy_from_neighbour(chunk, offset);
+                    }
+                }
+            }
+        }
+        Some(padded)
+    }
+
+    // Copies the part of a chunk that's inside the padded area, offset is where the chunk is relative to this one
+    fn copy_from_neighbour(&mut self, chunk: &Chunk, offset: ChunkPosition) {
+        // The range of local coordinates (in this chunk's space) that the neighbour covers on each axis
+        let range = |o: i32| match o {
+            -1 => (-1, -1),
+            0 => (0, CHUNK_SIZE_MIN1),
+            _ => (CHUNK_SIZE, CHUNK_SIZE),
+        };
+        let (min_x, max_x) = range(offset.x);
+        let (min_y, max_y) = range(offset.y);
+        let (min_z, max_z) = range(offset.z);
+        for y in min_y..=max_y {
+            for z in min_z..=max_z {
+                for x in min_x..=max_x {
+                    let local = glam::ivec3(x, y, z);
+                    self.voxels[PaddedChunk::coordinates_to_index(local)] = chunk.get_voxel_from_coordinate(local - offset * CHUNK_SIZE);
+                }
+            }
+        }
+        // States are stored sparsely, so go through the ones the chunk has rather than looking up every voxel
+        for (&index, &state) in &chunk.voxel_states {
+            let local = Chunk::index_to_coordinates(index) + offset * CHUNK_SIZE;
+            if !PaddedChunk::coordinate_out_of_bounds(local) {
+                self.states[PaddedChunk::coordinates_to_index(local)] = state;
+            }
+        }
+    }
+
+    // Turns local coordinates (-1 to 32 on each axis) into an index
+    pub fn coordinates_to_index(coordinate: VoxelPosition) -> usize {
+        let c = coordinate + 1;
+        ((PADDED_SIZE * PADDED_SIZE * c.y) + (PADDED_SIZE * c.z) + c.x) as usize
+    }
+    // Checks if a coordinate is outside of the padded area
+    pub fn coordinate_out_of_bounds(coordinate: VoxelPosition) -> bool {
+        coordinate.cmplt(VoxelPosition::splat(-1)).any() || coordinate.cmpgt(VoxelPosition::splat(CHUNK_SIZE)).any()
+    }
+    // Returns the voxel at local coordinates, these can be one voxel outside of the chunk
+    pub fn get_voxel(&self, coordinate: VoxelPosition) -> VoxelID {
+        assert!(!PaddedChunk::coordinate_out_of_bounds(coordinate), "Tried to get voxel outside of padded chunk!! {:?}", coordinate);
+        self.voxels[PaddedChunk::coordinates_to_index(coordinate)]
+    }
+    pub fn set_voxel(&mut self, coordinate: VoxelPosition, voxel_id: VoxelID) {
+        assert!(!PaddedChunk::coordinate_out_of_bounds(coordinate), "Tried to set voxel outside of padded chunk!! {:?}", coordinate);
+        self.voxels[PaddedChunk::coordinates_to_index(coordinate)] = voxel_id;
+    }
+    pub fn get_state(&self, coordinate: VoxelPosition) -> VoxelState {
+        assert!(!PaddedChunk::coordinate_out_of_bounds(coordinate), "Tried to get state outside of padded chunk!! {:?}", coordinate);
+        self.states[PaddedChunk::coordinates_to_index(coordinate)]
+    }
+    pub fn set_state(&mut self, coordinate: VoxelPosition, state: VoxelState) {
+        assert!(!PaddedChunk::coordinate_out_of_bounds(coordinate), "Tried to set state outside of padded chunk!! {:?}", coordinate);
+        self.states[PaddedChunk::coordinates_to_index(coordinate)] = state;
+    }
+}
@@ -0,0 +1,370 @@
+use glam::Vec3;
+
+use crate::{chunk::{VoxelPosition, VoxelID}, chunk_manager::ChunkManager};
+
+// Boxes closer than this are counted as touching, so floating point error can't push things into each other
+// (f32s lose precision quickly far from the origin, so this has to be fairly big)
+const EPSILON: f32 = 1e-3;
+
+// An axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+    // A box size wide centered on x and z, with its bottom at feet (how players and mobs are usually measured)
+    pub fn from_feet(feet: Vec3, size: Vec3) -> Self {
+        let half = glam::vec3(size.x / 2.0, 0.0, size.z / 2.0);
+        Self { min: feet - half, max: feet + glam::vec3(half.x, size.y, half.z) }
+    }
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+    // The middle of the bottom face
+    pub fn feet(&self) -> Vec3 {
+        glam::vec3((self.min.x + self.max.x) / 2.0, self.min.y, (self.min.z + self.max.z) / 2.0)
+    }
+    pub fn offset(&self, offset: Vec3) -> Self {
+        Self { min: self.min + offset, max: self.max + offset }
+    }
+    // The box covering this one and where it would be after moving by movement
+    pub fn swept(&self, movement: Vec3) -> Self {
+        Self { min: self.min + movement.min(Vec3::ZERO), max: self.max + movement.max(Vec3::ZERO) }
+    }
+    // Whether the boxes overlap, just touching doesn't count
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmplt(other.max).all() && self.max.cmpgt(other.min).all()
+    }
+
+    // How far this box can move along an axis (0 = x, 1 = y, 2 = z) before it hits other, starting from distance
+    // Boxes that don't overlap on the other two axes, or are already behind this one, don't stop it
+    pub fn clip_axis(&self, other: &Aabb, axis: usize, distance: f32) -> f32 {
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let overlapping = self.max[a] > other.min[a] && self.min[a] < other.max[a] &&
+            self.max[b] > other.min[b] && self.min[b] < other.max[b];
+        if !overlapping {
+            return distance;
+        }
+        if distance > 0.0 && self.max[axis] <= other.min[axis] + EPSILON {
+            distance.min(other.min[axis] - self.max[axis])
+        } else if distance < 0.0 && self.min[axis] >= other.max[axis] - EPSILON {
+            distance.max(other.max[axis] - self.min[axis])
+        } else {
+            distance
+        }
+    }
+}
+
+// What a voxel collides as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionShape {
+    // Fills the whole voxel
+    Full,
+    // Can be walked through (e.g. grass, fluids)
+    None,
+    // Part of the voxel, from 0 to 1 on each axis (e.g. a slab is (0, 0, 0) to (1, 0.5, 1))
+    Partial(Aabb),
+}
+
+// What happened when a box was moved through the world
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveResult {
+    // Where the box ended up
+    pub aabb: Aabb,
+    // How far it actually moved
+    pub movement: Vec3,
+    // Hit something below it
+    pub grounded: bool,
+    // Hit something above it
+    pub ceiling: bool,
+    // Hit something on the x or z axis
+    pub wall_x: bool,
+    pub wall_z: bool,
+    // Went up a ledge to keep moving
+    pub stepped: bool,
+}
+
+// The voxel a ray hit first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub voxel: VoxelPosition,
+    // Which way the face the ray went in through is facing (zero if the ray started inside the voxel)
+    // voxel + normal is the voxel in front of that face, where a block would be placed
+    pub normal: VoxelPosition,
+    // How far along the ray it was hit
+    pub distance: f32,
+}
+
+// Collision against the voxel world. Boxes are moved one axis at a time (y first, then x and z), and on each axis
+// they're stopped by the first voxel collision box in the way, so they can't go through anything however fast they go
+impl ChunkManager {
+    // The collision boxes of every voxel overlapping region, in world space
+    // Voxels that aren't loaded are treated as solid, so nothing falls out of the world before it's generated
+    pub fn get_collision_boxes(&self, region: &Aabb) -> Vec<Aabb> {
+        let min = (region.min - EPSILON).floor().as_ivec3();
+        let max = (region.max + EPSILON).floor().as_ivec3();
+        let mut boxes = vec![];
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    let pos = glam::ivec3(x, y, z);
+                    let shape = match self.get_voxel(pos) {
+                        Some(voxel_id) => self.voxel_data_manager.get_voxel_data(voxel_id).collision_shape,
+                        None => CollisionShape::Full,
+                    };
+                    match shape {
+                        CollisionShape::Full => boxes.push(Aabb::new(pos.as_vec3(), (pos + 1).as_vec3())),
+                        CollisionShape::Partial(shape) => boxes.push(shape.offset(pos.as_vec3())),
+                        CollisionShape::None => {}
+                    }
+                }
+            }
+        }
+        boxes
+    }
+
+    // Whether a voxel has anything to collide with
+    pub fn is_solid(&self, global_coord: VoxelPosition) -> bool {
+        match self.get_voxel(global_coord) {
+            Some(voxel_id) => self.voxel_data_manager.get_voxel_data(voxel_id).collision_shape != CollisionShape::None,
+            None => true,
+        }
+    }
+
+    // Moves a box as far as it can go towards movement
+    // If it's on the ground and walks into a ledge up to step_height tall, it's lifted up onto it (0 to never step up)
+    pub fn move_aabb(&self, aabb: Aabb, movement: Vec3, step_height: f32) -> MoveResult {
+        let result = self.sweep_aabb(aabb, movement);
+        let blocked = result.wall_x || result.wall_z;
+        let on_ground = result.grounded || (movement.y <= 0.0 && self.is_on_ground(&aabb));
+        if step_height <= 0.0 || !blocked || !on_ground {
+            return result;
+        }
+        // Try again from higher up: go up, then across, then back down onto whatever's there
+        let up = self.sweep_aabb(aabb, glam::vec3(0.0, step_height, 0.0));
+        let across = self.sweep_aabb(up.aabb, glam::vec3(movement.x, 0.0, movement.z));
+        let down = self.sweep_aabb(across.aabb, glam::vec3(0.0, movement.y.min(0.0) - up.movement.y, 0.0));
+        // Only keep the step if it got further than walking into the ledge did
+        let horizontal = |v: Vec3| glam::vec2(v.x, v.z).length_squared();
+        if horizontal(down.aabb.min - aabb.min) <= horizontal(result.movement) {
+            return result;
+        }
+        MoveResult {
+            aabb: down.aabb,
+            movement: down.aabb.min - aabb.min,
+            grounded: down.grounded,
+            ceiling: result.ceiling,
+            wall_x: across.wall_x,
+            wall_z: across.wall_z,
+            stepped: true,
+        }
+    }
+
+    // Whether there's anything right under a box
+    pub fn is_on_ground(&self, aabb: &Aabb) -> bool {
+        self.sweep_aabb(*aabb, glam::vec3(0.0, -EPSILON * 2.0, 0.0)).grounded
+    }
+
+    // Moves a box along each axis in turn, stopping it at the first thing it hits on each
+    fn sweep_aabb(&self, aabb: Aabb, movement: Vec3) -> MoveResult {
+        let boxes = self.get_collision_boxes(&aabb.swept(movement));
+        let mut moved = aabb;
+        let mut actual = Vec3::ZERO;
+        for axis in [1, 0, 2] {
+            if movement[axis] == 0.0 { continue; }
+            let distance = boxes.iter().fold(movement[axis], |d, b| moved.clip_axis(b, axis, d));
+            actual[axis] = distance;
+            let mut offset = Vec3::ZERO;
+            offset[axis] = distance;
+            moved = moved.offset(offset);
+        }
+        let clipped = |axis: usize| actual[axis] != movement[axis];
+        MoveResult {
+            aabb: moved,
+            movement: actual,
+            grounded: movement.y < 0.0 && clipped(1),
+            ceiling: movement.y > 0.0 && clipped(1),
+            wall_x: clipped(0),
+            wall_z: clipped(2),
+            stepped: false,
+        }
+    }
+
+    // Walks along a ray one voxel at a time (a DDA, so no voxel is skipped however thin the ray clips it) and returns
+    // the first voxel that is_target returns true for. Unloaded voxels stop the ray without hitting anything
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32, is_target: impl Fn(VoxelID) -> bool) -> Option<RaycastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec3::ZERO { return None; }
+        let mut voxel = origin.floor().as_ivec3();
+        let step = direction.signum().as_ivec3();
+        // How far along the ray it takes to cross a whole voxel on each axis, and to reach the next voxel boundary
+        let delta = direction.abs().recip();
+        let mut next_boundary = Vec3::ZERO;
+        for axis in 0..3 {
+            let boundary = if direction[axis] > 0.0 { voxel[axis] as f32 + 1.0 } else { voxel[axis] as f32 };
+            next_boundary[axis] = if direction[axis] == 0.0 { f32::INFINITY } else { (boundary - origin[axis]) / direction[axis] };
+        }
+        let mut normal = VoxelPosition::ZERO;
+        let mut distance = 0.0;
+        while distance <= max_distance {
+            let voxel_id = self.get_voxel(voxel)?;
+            if is_target(voxel_id) {
+                return Some(RaycastHit { voxel, normal, distance });
+            }
+            // Step into whichever voxel the ray reaches first
+            let axis = if next_boundary.x < next_boundary.y && next_boundary.x < next_boundary.z { 0 }
+                else if next_boundary.y < next_boundary.z { 1 }
+                else { 2 };
+            distance = next_boundary[axis];
+            next_boundary[axis] += delta[axis];
+            voxel[axis] += step[axis];
+            normal = VoxelPosition::ZERO;
+            normal[axis] = -step[axis];
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::{Chunk, DEFAULT_VOXELS}, voxel_data_manager::VoxelDataManager};
+
+    const PLAYER_SIZE: Vec3 = glam::vec3(0.6, 1.8, 0.6);
+
+    // One chunk with a stone floor along y = 0, Oak Planks collide as a bottom half slab
+    fn test_world() -> ChunkManager {
+        let mut voxel_data_manager = VoxelDataManager::with_default_voxels(&mut vec![]);
+        voxel_data_manager.configure("Oak Planks", |data| {
+            data.collision_shape = CollisionShape::Partial(Aabb::new(Vec3::ZERO, glam::vec3(1.0, 0.5, 1.0)));
+        });
+        let mut chunk_manager = ChunkManager::new_with_seed(voxel_data_manager, 0);
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        let mut voxels = DEFAULT_VOXELS;
+        for (index, voxel) in voxels.iter_mut().enumerate() {
+            if Chunk::index_to_coordinates(index).y == 0 {
+                *voxel = stone;
+            }
+        }
+        chunk_manager.set_chunk(VoxelPosition::ZERO, voxels);
+        chunk_manager
+    }
+
+    fn approx_eq(a: Vec3, b: Vec3) -> bool {
+        (a - b).abs().max_element() < 1e-4
+    }
+
+    #[test]
+    fn falling_lands_on_the_floor() {
+        let chunk_manager = test_world();
+        let aabb = Aabb::from_feet(glam::vec3(8.5, 3.0, 8.5), PLAYER_SIZE);
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(0.0, -10.0, 0.0), 0.0);
+        assert!(result.grounded);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(8.5, 1.0, 8.5)));
+        assert!(chunk_manager.is_on_ground(&result.aabb));
+    }
+
+    #[test]
+    fn steps_up_onto_slabs_but_not_full_blocks() {
+        let mut chunk_manager = test_world();
+        let ids = |name| chunk_manager.voxel_data_manager.get_id(name).unwrap();
+        let (stone, planks) = (ids("Stone"), ids("Oak Planks"));
+        chunk_manager.set_voxel(glam::ivec3(10, 1, 8), planks);
+        chunk_manager.set_voxel(glam::ivec3(10, 1, 12), stone);
+
+        let aabb = Aabb::from_feet(glam::vec3(9.5, 1.0, 8.5), PLAYER_SIZE);
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(0.5, 0.0, 0.0), 0.6);
+        assert!(result.stepped);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(10.0, 1.5, 8.5)));
+        // Without a step height it just walks into the slab
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(0.5, 0.0, 0.0), 0.0);
+        assert!(!result.stepped && result.wall_x);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(9.7, 1.0, 8.5)));
+
+        let aabb = Aabb::from_feet(glam::vec3(9.5, 1.0, 12.5), PLAYER_SIZE);
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(0.5, 0.0, 0.0), 0.6);
+        assert!(!result.stepped && result.wall_x);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(9.7, 1.0, 12.5)));
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let mut chunk_manager = test_world();
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        for z in 0..32 {
+            chunk_manager.set_voxel(glam::ivec3(10, 1, z), stone);
+            chunk_manager.set_voxel(glam::ivec3(10, 2, z), stone);
+        }
+        // Walking diagonally into the wall keeps the movement along it
+        let aabb = Aabb::from_feet(glam::vec3(9.5, 1.0, 8.5), PLAYER_SIZE);
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(1.0, 0.0, 1.0), 0.6);
+        assert!(result.wall_x && !result.wall_z && !result.stepped);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(9.7, 1.0, 9.5)));
+    }
+
+    #[test]
+    fn stops_in_inside_corners() {
+        let mut chunk_manager = test_world();
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        for y in 1..3 {
+            for i in 0..=10 {
+                chunk_manager.set_voxel(glam::ivec3(10, y, i), stone);
+                chunk_manager.set_voxel(glam::ivec3(i, y, 10), stone);
+            }
+        }
+        let aabb = Aabb::from_feet(glam::vec3(9.5, 1.0, 9.5), PLAYER_SIZE);
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(1.0, 0.0, 1.0), 0.6);
+        assert!(result.wall_x && result.wall_z);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(9.7, 1.0, 9.7)));
+    }
+
+    #[test]
+    fn doesnt_catch_on_outside_corners() {
+        let mut chunk_manager = test_world();
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        chunk_manager.set_voxel(glam::ivec3(10, 1, 10), stone);
+        chunk_manager.set_voxel(glam::ivec3(10, 2, 10), stone);
+        // Sliding along the side of a block and past its corner
+        let aabb = Aabb::from_feet(glam::vec3(9.7, 1.0, 10.5), PLAYER_SIZE);
+        let result = chunk_manager.move_aabb(aabb, glam::vec3(0.0, 0.0, 2.0), 0.6);
+        assert!(!result.wall_x && !result.wall_z);
+        assert!(approx_eq(result.aabb.feet(), glam::vec3(9.7, 1.0, 12.5)));
+    }
+
+    #[test]
+    fn raycasts_hit_the_face_they_go_in_through() {
+        let mut chunk_manager = test_world();
+        let stone = chunk_manager.voxel_data_manager.get_id("Stone").unwrap();
+        chunk_manager.set_voxel(glam::ivec3(10, 5, 8), stone);
+        let is_solid = |v| v != 0;
+
+        let hit = chunk_manager.raycast(glam::vec3(8.5, 5.5, 8.5), Vec3::X, 6.0, is_solid).unwrap();
+        assert_eq!((hit.voxel, hit.normal, hit.distance), (glam::ivec3(10, 5, 8), glam::ivec3(-1, 0, 0), 1.5));
+        let hit = chunk_manager.raycast(glam::vec3(12.5, 5.5, 8.5), Vec3::NEG_X, 6.0, is_solid).unwrap();
+        assert_eq!((hit.voxel, hit.normal), (glam::ivec3(10, 5, 8), glam::ivec3(1, 0, 0)));
+        let hit = chunk_manager.raycast(glam::vec3(10.5, 8.5, 8.5), Vec3::NEG_Y, 6.0, is_solid).unwrap();
+        assert_eq!((hit.voxel, hit.normal, hit.distance), (glam::ivec3(10, 5, 8), glam::ivec3(0, 1, 0), 2.5));
+        // Only just clipping the block's corner still hits it, through the side it crossed into last
+        let hit = chunk_manager.raycast(glam::vec3(9.95, 5.5, 7.05), glam::vec3(1.0, 0.0, 1.0), 6.0, is_solid).unwrap();
+        assert_eq!((hit.voxel, hit.normal), (glam::ivec3(10, 5, 8), glam::ivec3(0, 0, -1)));
+    }
+
+    #[test]
+    fn raycasts_stop_at_their_reach_and_unloaded_voxels() {
+        let chunk_manager = test_world();
+        let is_solid = |v| v != 0;
+        assert_eq!(chunk_manager.raycast(glam::vec3(8.5, 9.5, 8.5), Vec3::NEG_Y, 6.0, is_solid), None);
+        assert!(chunk_manager.raycast(glam::vec3(8.5, 9.5, 8.5), Vec3::NEG_Y, 10.0, is_solid).is_some());
+        // Nothing but air until it leaves the loaded chunk
+        assert_eq!(chunk_manager.raycast(glam::vec3(8.5, 9.5, 8.5), Vec3::Y, 100.0, is_solid), None);
+        assert_eq!(chunk_manager.raycast(glam::vec3(8.5, 9.5, 8.5), Vec3::ZERO, 100.0, is_solid), None);
+        // Starting inside a block hits it straight away, with no face to place against
+        let hit = chunk_manager.raycast(glam::vec3(8.5, 0.5, 8.5), Vec3::X, 6.0, is_solid).unwrap();
+        assert_eq!((hit.voxel, hit.normal, hit.distance), (glam::ivec3(8, 0, 8), VoxelPosition::ZERO, 0.0));
+    }
+}
@@ -0,0 +1,203 @@
+use glam::Vec3;
+
+use crate::{camera::Camera, chunk_manager::ChunkManager, physics::Aabb, input_actions::{Action, Actions}};
+
+// The player's collision box, and how high their eyes are above their feet
+pub const PLAYER_SIZE: Vec3 = glam::vec3(0.6, 1.8, 0.6);
+pub const EYE_HEIGHT: f32 = 1.62;
+pub const CROUCH_EYE_HEIGHT: f32 = 1.27;
+// How far from their eyes the player can break and place blocks
+pub const REACH: f32 = 6.0;
+
+// Speeds are in voxels per second, accelerations in voxels per second squared
+pub const WALK_SPEED: f32 = 4.3;
+pub const SPRINT_SPEED: f32 = 5.6;
+pub const CROUCH_SPEED: f32 = 1.3;
+pub const SWIM_SPEED: f32 = 2.0;
+pub const GROUND_ACCELERATION: f32 = 40.0;
+pub const AIR_ACCELERATION: f32 = 8.0;
+pub const GRAVITY: f32 = 28.0;
+pub const TERMINAL_VELOCITY: f32 = 60.0;
+pub const JUMP_SPEED: f32 = 8.5;
+// Fluids slow everything down and make you sink slowly
+pub const FLUID_GRAVITY: f32 = 6.0;
+pub const FLUID_DRAG: f32 = 3.0;
+// How far the edge guard moves you back at a time when you're crouching off a ledge
+const EDGE_GUARD_STEP: f32 = 0.05;
+// Longer frames are split up, so a lag spike can't throw you through the floor or over a wall
+const MAX_STEP: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    Walk,
+    Fly,
+}
+
+// Walks a camera around the world with collision. It doesn't own the camera, so switching between this and the
+// FlyCamera keeps the same position - the player's feet are always worked out from where the camera is
+pub struct PlayerController {
+    pub mode: MovementMode,
+    pub velocity: Vec3,
+    pub step_height: f32,
+    pub grounded: bool,
+    pub in_fluid: bool,
+    pub crouching: bool,
+    pub sprinting: bool,
+}
+
+impl PlayerController {
+    pub fn new() -> Self {
+        Self { mode: MovementMode::Fly, velocity: Vec3::ZERO, step_height: 0.6, grounded: false, in_fluid: false, crouching: false, sprinting: false }
+    }
+
+    // Switches between walking and flying, any speed the player had is lost
+    pub fn toggle_mode(&mut self) -> MovementMode {
+        self.mode = match self.mode {
+            MovementMode::Walk => MovementMode::Fly,
+            MovementMode::Fly => MovementMode::Walk,
+        };
+        self.velocity = Vec3::ZERO;
+        self.grounded = false;
+        self.mode
+    }
+
+    pub fn eye_height(&self) -> f32 {
+        if self.crouching { CROUCH_EYE_HEIGHT } else { EYE_HEIGHT }
+    }
+    // The player's collision box, going from where the camera is
+    pub fn get_aabb(&self, camera: &Camera) -> Aabb {
+        Aabb::from_feet(camera.position - glam::vec3(0.0, self.eye_height(), 0.0), PLAYER_SIZE)
+    }
+
+    // Moves the camera from the player's actions, only does anything in walk mode
+    pub fn handle_movement(&mut self, camera: &mut Camera, actions: &Actions, deltatime: f32, chunk_manager: &ChunkManager) {
+        if self.mode != MovementMode::Walk { return; }
+        // Which way the player wants to go, relative to the way they're looking
+        let (sin_rot, cos_rot) = camera.rotation.y.sin_cos();
+        let forward = glam::vec2(-sin_rot, cos_rot);
+        let right = glam::vec2(cos_rot, sin_rot);
+        let mut wish = glam::Vec2::ZERO;
+        if actions.action_held(Action::MoveForward) { wish += forward; }
+        if actions.action_held(Action::MoveBackward) { wish -= forward; }
+        if actions.action_held(Action::MoveRight) { wish += right; }
+        if actions.action_held(Action::MoveLeft) { wish -= right; }
+        let wish = wish.normalize_or_zero();
+        let jumping = actions.action_held(Action::Jump);
+        // Crouching changes where the feet are relative to the camera, so move the camera rather than the feet
+        let feet = self.get_aabb(camera).feet();
+        self.crouching = actions.action_held(Action::Crouch);
+        self.sprinting = actions.action_held(Action::Sprint) && !self.crouching && wish.dot(forward) > 0.0;
+        camera.position = feet + glam::vec3(0.0, self.eye_height(), 0.0);
+
+        let mut remaining = deltatime;
+        while remaining > 0.0 {
+            let dt = remaining.min(MAX_STEP);
+            remaining -= dt;
+            self.step(camera, wish, jumping, dt, chunk_manager);
+        }
+    }
+
+    fn step(&mut self, camera: &mut Camera, wish: glam::Vec2, jumping: bool, dt: f32, chunk_manager: &ChunkManager) {
+        let aabb = self.get_aabb(camera);
+        self.in_fluid = chunk_manager.is_in_fluid(&aabb);
+
+        // Speed up or slow down towards the speed the player wants to go at, how fast depends on what's underfoot
+        let speed = if self.in_fluid { SWIM_SPEED }
+            else if self.crouching { CROUCH_SPEED }
+            else if self.sprinting { SPRINT_SPEED }
+            else { WALK_SPEED };
+        let acceleration = if self.grounded {
+            GROUND_ACCELERATION * chunk_manager.get_friction_under(&aabb)
+        } else {
+            AIR_ACCELERATION
+        };
+        let horizontal = move_towards(glam::vec2(self.velocity.x, self.velocity.z), wish * speed, acceleration * dt);
+        self.velocity.x = horizontal.x;
+        self.velocity.z = horizontal.y;
+
+        if self.in_fluid {
+            // Swim up while jump is held, otherwise sink slowly
+            self.velocity.y -= FLUID_GRAVITY * dt;
+            self.velocity.y -= self.velocity.y * (FLUID_DRAG * dt).min(1.0);
+            if jumping {
+                self.velocity.y = self.velocity.y.max(SWIM_SPEED);
+            }
+        } else {
+            self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+            if jumping && self.grounded {
+                self.velocity.y = JUMP_SPEED;
+            }
+        }
+
+        let mut movement = self.velocity * dt;
+        if self.crouching && self.grounded && !self.in_fluid {
+            movement = self.guard_edges(&aabb, movement, chunk_manager);
+        }
+        let step_height = if self.grounded { self.step_height } else { 0.0 };
+        let result = chunk_manager.move_aabb(aabb, movement, step_height);
+
+        // Lose speed in any direction something got in the way
+        if result.grounded || result.ceiling { self.velocity.y = 0.0; }
+        if result.wall_x { self.velocity.x = 0.0; }
+        if result.wall_z { self.velocity.z = 0.0; }
+        self.grounded = result.grounded || (self.velocity.y <= 0.0 && chunk_manager.is_on_ground(&result.aabb));
+        camera.position = result.aabb.feet() + glam::vec3(0.0, self.eye_height(), 0.0);
+    }
+
+    // Cuts down horizontal movement that would take the player off the edge of what they're standing on
+    fn guard_edges(&self, aabb: &Aabb, mut movement: Vec3, chunk_manager: &ChunkManager) -> Vec3 {
+        let on_ground_after = |x: f32, z: f32| chunk_manager.is_on_ground(&aabb.offset(glam::vec3(x, 0.0, z)));
+        let shrink = |d: f32| if d.abs() <= EDGE_GUARD_STEP { 0.0 } else { d - EDGE_GUARD_STEP * d.signum() };
+        while movement.x != 0.0 && !on_ground_after(movement.x, 0.0) {
+            movement.x = shrink(movement.x);
+        }
+        while movement.z != 0.0 && !on_ground_after(0.0, movement.z) {
+            movement.z = shrink(movement.z);
+        }
+        while movement.x != 0.0 && movement.z != 0.0 && !on_ground_after(movement.x, movement.z) {
+            movement.x = shrink(movement.x);
+            movement.z = shrink(movement.z);
+        }
+        movement
+    }
+}
+
+impl Default for PlayerController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Moves current towards target by at most max_delta
+fn move_towards(current: glam::Vec2, target: glam::Vec2, max_delta: f32) -> glam::Vec2 {
+    let difference = target - current;
+    let distance = difference.length();
+    if distance <= max_delta || distance == 0.0 {
+        target
+    } else {
+        current + difference / distance * max_delta
+    }
+}
+
+impl ChunkManager {
+    // Whether any part of a box is in a fluid
+    pub fn is_in_fluid(&self, aabb: &Aabb) -> bool {
+        let min = aabb.min.floor().as_ivec3();
+        let max = aabb.max.floor().as_ivec3();
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    if self.get_voxel(glam::ivec3(x, y, z)).is_some_and(|v| self.get_fluid(v).is_some()) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+    // How grippy the voxel under the middle of a box is, 1 if there's nothing there
+    pub fn get_friction_under(&self, aabb: &Aabb) -> f32 {
+        let below = (aabb.feet() - glam::vec3(0.0, 0.01, 0.0)).floor().as_ivec3();
+        self.get_voxel(below).map_or(1.0, |v| self.voxel_data_manager.get_voxel_data(v).friction)
+    }
+}
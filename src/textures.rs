@@ -0,0 +1,248 @@
+use glam::Vec2;
+use image::{Rgba, RgbaImage};
+
+// Cutout faces are drawn with anything at or under this alpha discarded
+pub const ALPHA_CUTOFF: f32 = 0.5;
+
+// Textures are stored in sRGB, so anything that mixes or darkens colours has to do it on the linear values
+pub fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// How many mip levels a texture this big has, down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Halves a texture by averaging each 2x2 block (the last row or column is repeated for odd sizes)
+// Colours are weighted by their alpha, otherwise the invisible colour of see-through texels (usually black)
+// bleeds into the edges of cutouts like leaves
+pub fn downsample(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = ((image.width() / 2).max(1), (image.height() / 2).max(1));
+    RgbaImage::from_fn(width, height, |x, y| {
+        let mut colour = [0.0; 3];
+        let mut unweighted = [0.0; 3];
+        let mut alpha = 0.0;
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let texel = image.get_pixel((x * 2 + dx).min(image.width() - 1), (y * 2 + dy).min(image.height() - 1)).0;
+            let texel_alpha = texel[3] as f32 / 255.0;
+            for c in 0..3 {
+                colour[c] += srgb_to_linear(texel[c]) * texel_alpha;
+                unweighted[c] += srgb_to_linear(texel[c]);
+            }
+            alpha += texel_alpha;
+        }
+        // Fully see-through blocks keep a plain average, so filtering into them doesn't fade to black
+        let rgb = if alpha > 0.0 { colour.map(|c| c / alpha) } else { unweighted.map(|c| c / 4.0) };
+        Rgba([linear_to_srgb(rgb[0]), linear_to_srgb(rgb[1]), linear_to_srgb(rgb[2]), ((alpha / 4.0) * 255.0).round() as u8])
+    })
+}
+
+// The fraction of texels that'd pass an alpha test at cutoff (the shader discards anything at or under it)
+pub fn alpha_coverage(image: &RgbaImage, cutoff: f32, alpha_scale: f32) -> f32 {
+    let passing = image.pixels().filter(|p| (p.0[3] as f32 / 255.0 * alpha_scale).min(1.0) > cutoff).count();
+    passing as f32 / (image.width() * image.height()) as f32
+}
+
+// Scales a mip's alpha up so the same fraction of it passes the alpha test as the full size texture does
+// Averaging makes cutouts more and more see-through at each level, so without this leaves and grass thin out
+// and vanish in the distance. It's never scaled down, cutouts are alpha blended so that'd fade out solid texels
+pub fn preserve_alpha_coverage(image: &mut RgbaImage, cutoff: f32, target_coverage: f32) {
+    let (mut low, mut high) = (1.0f32, 4.0f32);
+    for _ in 0..16 {
+        let middle = (low + high) / 2.0;
+        if alpha_coverage(image, cutoff, middle) < target_coverage { low = middle; } else { high = middle; }
+    }
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = ((pixel.0[3] as f32 * high).min(255.0)).round() as u8;
+    }
+}
+
+// Every mip level of a texture, starting with the texture itself
+// alpha_cutoff should be the cutoff the texture's drawn with if it's a cutout, to keep its coverage the same
+pub fn build_mip_chain(image: &RgbaImage, alpha_cutoff: Option<f32>) -> Vec<RgbaImage> {
+    let target_coverage = alpha_cutoff.map(|cutoff| (cutoff, alpha_coverage(image, cutoff, 1.0)));
+    let mut levels = vec![image.clone()];
+    for _ in 1..mip_level_count(image.width(), image.height()) {
+        let mut level = downsample(levels.last().unwrap());
+        if let Some((cutoff, coverage)) = target_coverage {
+            preserve_alpha_coverage(&mut level, cutoff, coverage);
+        }
+        levels.push(level);
+    }
+    levels
+}
+
+// Uploads mip levels 1 and up of each layer, level 0 should already be in the texture
+// (e.g. from SrgbTexture2dArray::with_mipmaps with MipmapsOption::EmptyMipmaps)
+// glium can't write to one layer of an array's mip level directly, so it goes through a pixel buffer
+pub fn write_array_mipmaps<F: glium::backend::Facade + ?Sized>(facade: &F, texture: &glium::texture::SrgbTexture2dArray, layers: &[Vec<RgbaImage>]) {
+    for (layer, levels) in layers.iter().enumerate() {
+        for (level, image) in levels.iter().enumerate().skip(1) {
+            let Some(mipmap) = (**texture).mipmap(level as u32) else { break; };
+            // Uploaded upside down like the full size textures are
+            let flipped = image::imageops::flip_vertical(image);
+            let texels: Vec<(u8, u8, u8, u8)> = flipped.pixels().map(|p| (p.0[0], p.0[1], p.0[2], p.0[3])).collect();
+            let buffer = glium::texture::pixel_buffer::PixelBuffer::new_empty(facade, texels.len());
+            buffer.write(&texels);
+            mipmap.raw_upload_from_pixel_buffer(buffer.as_slice(), 0..image.width(), 0..image.height(), layer as u32..layer as u32 + 1);
+        }
+    }
+}
+
+// Every texture packed into one 2D texture, for anything that can't use texture arrays
+// Each tile's surrounded by padding texels copied from its edges, so filtering and lower mips don't pick up
+// the texels of the tiles next to it. Every texture has to be the same size, like for a texture array
+pub struct TextureAtlas {
+    // The atlas at each mip level, built from the textures' own mips rather than shrinking the whole atlas
+    pub levels: Vec<RgbaImage>,
+    pub tile_size: u32,
+    pub padding: u32,
+    pub columns: u32,
+    pub texture_count: usize,
+}
+
+impl TextureAtlas {
+    // mip_chains is every texture's mip levels (see build_mip_chain). Levels stop once the padding would go under a
+    // texel, so padding should be a power of two (e.g. 4 gives 3 levels)
+    pub fn pack(mip_chains: &[Vec<RgbaImage>], padding: u32) -> Self {
+        let tile_size = mip_chains.first().map(|levels| levels[0].width()).unwrap_or(1);
+        let columns = ((mip_chains.len() as f32).sqrt().ceil() as u32).max(1);
+        let rows = (mip_chains.len() as u32).div_ceil(columns).max(1);
+        let level_count = mip_chains.iter().map(|levels| levels.len() as u32).min().unwrap_or(1)
+            .min(32 - padding.max(1).leading_zeros());
+
+        let mut levels = vec![];
+        for level in 0..level_count {
+            let (tile, pad) = ((tile_size >> level).max(1), padding >> level);
+            let cell = tile + pad * 2;
+            let mut atlas = RgbaImage::new(cell * columns, cell * rows);
+            for (index, mips) in mip_chains.iter().enumerate() {
+                let image = &mips[level as usize];
+                let origin = ((index as u32 % columns) * cell, (index as u32 / columns) * cell);
+                for y in 0..cell {
+                    for x in 0..cell {
+                        let source = ((x as i32 - pad as i32).clamp(0, tile as i32 - 1), (y as i32 - pad as i32).clamp(0, tile as i32 - 1));
+                        atlas.put_pixel(origin.0 + x, origin.1 + y, *image.get_pixel(source.0 as u32, source.1 as u32));
+                    }
+                }
+            }
+            levels.push(atlas);
+        }
+        Self { levels, tile_size, padding, columns, texture_count: mip_chains.len() }
+    }
+
+    // Where a texture is in the atlas (not counting its padding), from its top left corner to its bottom right
+    // It's the same at every mip level
+    pub fn tex_coords(&self, texture_id: u32) -> (Vec2, Vec2) {
+        let cell = self.tile_size + self.padding * 2;
+        let atlas_size = glam::vec2(self.levels[0].width() as f32, self.levels[0].height() as f32);
+        let min = glam::vec2(((texture_id % self.columns) * cell + self.padding) as f32, ((texture_id / self.columns) * cell + self.padding) as f32);
+        (min / atlas_size, (min + self.tile_size as f32) / atlas_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A cutout texture with a scattering of see-through holes in it, like leaves
+    fn cutout_texture(size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            let alpha = if (x * 7 + y * 13) % 5 < 2 { 0 } else { 255 };
+            Rgba([60, 140 + (x * 4) as u8, 40, alpha])
+        })
+    }
+    // Every alpha from 0 to 255 once, in a jumbled order
+    fn alpha_spread() -> RgbaImage {
+        RgbaImage::from_fn(16, 16, |x, y| Rgba([255, 255, 255, ((x + y * 16) * 37 % 256) as u8]))
+    }
+
+    #[test]
+    fn mip_levels_go_down_to_one_texel() {
+        assert_eq!(mip_level_count(16, 16), 5);
+        assert_eq!(mip_level_count(16, 4), 5);
+        assert_eq!(mip_level_count(17, 1), 5);
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(0, 0), 1);
+        let levels = build_mip_chain(&cutout_texture(16), None);
+        assert_eq!(levels.iter().map(|l| l.width()).collect::<Vec<_>>(), [16, 8, 4, 2, 1]);
+    }
+
+    #[test]
+    fn downsampling_ignores_the_colour_of_see_through_texels() {
+        let image = RgbaImage::from_fn(2, 2, |x, y| if (x, y) == (0, 0) { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 0, 0]) });
+        assert_eq!(downsample(&image).get_pixel(0, 0).0, [255, 0, 0, 64]);
+        // With nothing to see, the colour's a plain average so it doesn't fade to black when filtered
+        let image = RgbaImage::from_fn(2, 2, |x, _| Rgba([if x == 0 { 255 } else { 0 }, 0, 0, 0]));
+        let texel = downsample(&image).get_pixel(0, 0).0;
+        assert_eq!(texel[3], 0);
+        assert_eq!(texel[0], linear_to_srgb(0.5));
+    }
+
+    #[test]
+    fn one_texel_wide_textures_repeat_their_edge() {
+        let image = RgbaImage::from_fn(1, 2, |_, y| Rgba([[255, 0][y as usize], 0, 0, 255]));
+        let half = downsample(&image);
+        assert_eq!(half.dimensions(), (1, 1));
+        assert_eq!(half.get_pixel(0, 0).0, [linear_to_srgb(0.5), 0, 0, 255]);
+    }
+
+    #[test]
+    fn coverage_is_scaled_up_to_the_target() {
+        // With every alpha there, any coverage it can be scaled up to is hit to within a texel or two
+        for target in [0.6, 0.75, 0.85] {
+            let mut image = alpha_spread();
+            preserve_alpha_coverage(&mut image, ALPHA_CUTOFF, target);
+            let coverage = alpha_coverage(&image, ALPHA_CUTOFF, 1.0);
+            assert!(coverage >= target && coverage - target <= 2.0 / 256.0, "wanted {} but got {}", target, coverage);
+        }
+        // Already covering more than that, so it's left alone
+        let mut image = alpha_spread();
+        preserve_alpha_coverage(&mut image, ALPHA_CUTOFF, 0.3);
+        assert_eq!(image, alpha_spread());
+    }
+
+    #[test]
+    fn sparse_cutouts_dont_vanish_in_the_distance() {
+        // One opaque texel in every 2x2 block, which plain averaging turns see-through everywhere
+        let image = RgbaImage::from_fn(16, 16, |x, y| Rgba([60, 160, 40, if (x % 2, y % 2) == ((y / 2) % 2, 0) { 255 } else { 0 }]));
+        assert_eq!(alpha_coverage(&image, ALPHA_CUTOFF, 1.0), 0.25);
+        assert_eq!(alpha_coverage(&build_mip_chain(&image, None)[1], ALPHA_CUTOFF, 1.0), 0.0);
+        let preserved = build_mip_chain(&image, Some(ALPHA_CUTOFF));
+        for level in &preserved[1..] {
+            assert!(alpha_coverage(level, ALPHA_CUTOFF, 1.0) > 0.0);
+        }
+        // Solid textures don't get faded out
+        let solid = build_mip_chain(&RgbaImage::from_pixel(16, 16, Rgba([1, 2, 3, 255])), Some(ALPHA_CUTOFF));
+        assert!(solid.iter().all(|level| level.pixels().all(|p| p.0[3] == 255)));
+    }
+
+    #[test]
+    fn packed_textures_are_padded_with_their_edges() {
+        let red = RgbaImage::from_fn(4, 4, |x, y| Rgba([255, (x * 10) as u8, (y * 10) as u8, 255]));
+        let blue = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255]));
+        let atlas = TextureAtlas::pack(&[build_mip_chain(&red, None), build_mip_chain(&blue, None)], 2);
+        // Two tiles side by side, each 4 texels with 2 of padding either side
+        assert_eq!(atlas.columns, 2);
+        assert_eq!(atlas.levels[0].dimensions(), (16, 8));
+        // Only as many levels as the padding lasts for
+        assert_eq!(atlas.levels.len(), 2);
+        assert_eq!(atlas.levels[1].dimensions(), (8, 4));
+
+        let level = &atlas.levels[0];
+        assert_eq!(level.get_pixel(2, 2), red.get_pixel(0, 0));
+        assert_eq!(level.get_pixel(0, 0), red.get_pixel(0, 0));
+        assert_eq!(level.get_pixel(7, 3), red.get_pixel(3, 1));
+        assert_eq!(level.get_pixel(8, 0), blue.get_pixel(0, 0));
+
+        let (min, max) = atlas.tex_coords(1);
+        assert_eq!(min, glam::vec2(10.0 / 16.0, 2.0 / 8.0));
+        assert_eq!(max, glam::vec2(14.0 / 16.0, 6.0 / 8.0));
+    }
+}
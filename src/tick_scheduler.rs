@@ -0,0 +1,79 @@
+use crate::{chunk::{Convert, Chunk, ChunkPosition, VoxelPosition, CHUNK_SIZE_USIZE}, chunk_manager::ChunkManager, block_updates::BLOCK_UPDATES_PER_TICK};
+
+// What gets called when a voxel is ticked, with the voxel's global position
+pub type TickHandler = fn(&mut ChunkManager, VoxelPosition);
+
+// Ticks are how voxels change over time, there are two kinds:
+// - Scheduled ticks, where a voxel asks to be ticked after a certain number of ticks (e.g. fluids flowing)
+// - Random ticks, where random_tick_speed random voxels in every loaded chunk are ticked each tick (e.g. grass spreading)
+// Which handler gets called is set per voxel in the VoxelDataManager
+impl ChunkManager {
+    // Asks for the voxel at global_coord to be ticked after delay ticks, if it's already waiting the soonest time is kept
+    // The tick is stored in the voxel's chunk, so it's kept with it. Returns false if the chunk isn't loaded
+    pub fn schedule_tick(&mut self, global_coord: VoxelPosition, delay: u64) -> bool {
+        let due = self.tick_count + delay.max(1);
+        match self.get_chunk_mut(Convert::global_to_chunk(global_coord)) {
+            Some(chunk) => {
+                chunk.scheduled_ticks.entry(Convert::global_to_local(global_coord))
+                    .and_modify(|t| *t = (*t).min(due))
+                    .or_insert(due);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Whether the voxel at global_coord is waiting for a scheduled tick
+    pub fn has_scheduled_tick(&self, global_coord: VoxelPosition) -> bool {
+        self.get_chunk(Convert::global_to_chunk(global_coord))
+            .is_some_and(|chunk| chunk.scheduled_ticks.contains_key(&Convert::global_to_local(global_coord)))
+    }
+
+    // Moves the world forward by one tick
+    pub fn tick(&mut self) {
+        self.tick_count += 1;
+        self.process_block_updates(BLOCK_UPDATES_PER_TICK);
+        self.run_scheduled_ticks();
+        self.tick_explosives();
+        self.step_falling_blocks();
+        self.run_random_ticks();
+    }
+
+    fn run_scheduled_ticks(&mut self) {
+        let tick = self.tick_count;
+        let mut due: Vec<(u64, VoxelPosition)> = vec![];
+        for chunk in self.chunks.values_mut() {
+            let position = chunk.position;
+            chunk.scheduled_ticks.retain(|&local, &mut t| {
+                if t <= tick {
+                    due.push((t, Convert::local_to_global(position, local)));
+                }
+                t > tick
+            });
+        }
+        // HashMap order is random, so sort to make sure ticks always happen in the same order
+        due.sort_by_key(|&(t, p)| (t, p.y, p.z, p.x));
+        for (_, global_coord) in due {
+            let Some(voxel_id) = self.get_voxel(global_coord) else { continue; };
+            if let Some(handler) = self.voxel_data_manager.get_voxel_data(voxel_id).on_scheduled_tick {
+                handler(self, global_coord);
+            }
+        }
+    }
+
+    fn run_random_ticks(&mut self) {
+        let mut chunk_positions: Vec<ChunkPosition> = self.chunks.keys().copied().collect();
+        chunk_positions.sort_by_key(|p| (p.y, p.z, p.x));
+        let volume = (CHUNK_SIZE_USIZE * CHUNK_SIZE_USIZE * CHUNK_SIZE_USIZE) as i32;
+        for chunk_position in chunk_positions {
+            for _ in 0..self.random_tick_speed {
+                let index = self.tick_rng.range(0, volume) as usize;
+                let Some(chunk) = self.get_chunk(chunk_position) else { break; };
+                let voxel_id = chunk.get_voxel_from_index(index);
+                if let Some(handler) = self.voxel_data_manager.get_voxel_data(voxel_id).on_random_tick {
+                    handler(self, Convert::local_to_global(chunk_position, Chunk::index_to_coordinates(index)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+#version 140
+
+in vec2 v_tex_coords;
+in vec4 v_colour;
+flat in int v_layer;
+
+out vec4 color;
+
+uniform sampler2D font;
+uniform sampler2D icons;
+uniform sampler2DArray texture_array;
+
+void main() {
+    if (v_layer == -3) { // Block icons
+        color = texture(icons, v_tex_coords) * v_colour;
+    } else if (v_layer == -2) { // Plain colour
+        color = v_colour;
+    } else if (v_layer == -1) { // Font
+        color = texture(font, v_tex_coords) * v_colour;
+    } else { // Voxel textures
+        color = texture(texture_array, vec3(v_tex_coords, float(v_layer))) * v_colour;
+    }
+    if (color.a <= 0.0) {
+        discard;
+    }
+}
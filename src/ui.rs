@@ -0,0 +1,330 @@
+use glam::Vec2;
+use glium::implement_vertex;
+
+use crate::{inventory::{Hotbar, HOTBAR_SIZE}, voxel_data_manager::VoxelDataManager, camera::Camera, chunk::{Convert, VoxelPosition},
+    metrics::{Metrics, FRAME_HISTORY}, block_icons::IconAtlas};
+
+// What a UI vertex is textured with, anything 0 or over is a layer of the voxel texture array
+pub const UI_TEXTURE_ICONS: i32 = -3;
+pub const UI_TEXTURE_NONE: i32 = -2;
+pub const UI_TEXTURE_FONT: i32 = -1;
+
+// res/font.png holds the printable ASCII characters (32 to 126) in rows of 16, each 8x16 pixels
+// (the Sony Fixed 8x16 X11 font)
+pub const GLYPH_SIZE: Vec2 = glam::vec2(8.0, 16.0);
+pub const FONT_COLUMNS: u32 = 16;
+pub const FONT_ROWS: u32 = 6;
+const FIRST_CHAR: u32 = 32;
+const LAST_CHAR: u32 = 126;
+
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const PANEL_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+// Positions are in pixels from the top left of the screen, the shader turns them into clip space
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UiVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub colour: [f32; 4],
+    pub layer: i32,
+}
+implement_vertex!(UiVertex, position, tex_coords, colour, layer);
+
+// A rectangle on the screen, in pixels from the top left
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position, size }
+    }
+    // A rect size big with its middle at center
+    pub fn centered(center: Vec2, size: Vec2) -> Self {
+        Self { position: center - size / 2.0, size }
+    }
+    pub fn max(&self) -> Vec2 {
+        self.position + self.size
+    }
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.position).all() && point.cmplt(self.max()).all()
+    }
+    // The same rect shrunk by amount on every side (negative grows it)
+    pub fn inset(&self, amount: f32) -> Self {
+        Self { position: self.position + amount, size: (self.size - amount * 2.0).max(Vec2::ZERO) }
+    }
+}
+
+// Immediate mode UI: everything's added again every frame and comes out as one mesh, drawn over the world.
+// Nothing in here touches the GPU, so layouts can be checked without a window
+pub struct Ui {
+    pub screen_size: Vec2,
+    pub vertices: Vec<UiVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Ui {
+    pub fn new(screen_size: Vec2) -> Self {
+        Self { screen_size, vertices: vec![], indices: vec![] }
+    }
+
+    // tex_coords go from the top left corner of the texture to the bottom right
+    pub fn quad(&mut self, rect: Rect, tex_min: Vec2, tex_max: Vec2, layer: i32, colour: [f32; 4]) {
+        let first = self.vertices.len() as u32;
+        let (min, max) = (rect.position, rect.max());
+        for (position, tex_coords) in [
+            (min, tex_min),
+            (glam::vec2(max.x, min.y), glam::vec2(tex_max.x, tex_min.y)),
+            (max, tex_max),
+            (glam::vec2(min.x, max.y), glam::vec2(tex_min.x, tex_max.y)),
+        ] {
+            self.vertices.push(UiVertex { position: position.to_array(), tex_coords: tex_coords.to_array(), colour, layer });
+        }
+        self.indices.extend([first, first + 1, first + 2, first + 2, first + 3, first]);
+    }
+    pub fn rect(&mut self, rect: Rect, colour: [f32; 4]) {
+        self.quad(rect, Vec2::ZERO, Vec2::ONE, UI_TEXTURE_NONE, colour);
+    }
+    // A rect's outline, thickness pixels wide on the inside of it
+    pub fn rect_outline(&mut self, rect: Rect, thickness: f32, colour: [f32; 4]) {
+        let (min, size) = (rect.position, rect.size);
+        self.rect(Rect::new(min, glam::vec2(size.x, thickness)), colour);
+        self.rect(Rect::new(glam::vec2(min.x, rect.max().y - thickness), glam::vec2(size.x, thickness)), colour);
+        self.rect(Rect::new(min + glam::vec2(0.0, thickness), glam::vec2(thickness, size.y - thickness * 2.0)), colour);
+        self.rect(Rect::new(glam::vec2(rect.max().x - thickness, min.y + thickness), glam::vec2(thickness, size.y - thickness * 2.0)), colour);
+    }
+    // A voxel's icon from the block icon atlas
+    pub fn icon(&mut self, rect: Rect, icons: &IconAtlas, voxel_id: crate::chunk::VoxelID, colour: [f32; 4]) {
+        let (tex_min, tex_max) = icons.tex_coords(voxel_id);
+        self.quad(rect, tex_min, tex_max, UI_TEXTURE_ICONS, colour);
+    }
+    // A layer of the voxel texture array
+    pub fn image(&mut self, rect: Rect, layer: u32, colour: [f32; 4]) {
+        self.quad(rect, Vec2::ZERO, Vec2::ONE, layer as i32, colour);
+    }
+
+    // How big some text would be, the widest line by the number of lines
+    pub fn text_size(text: &str, scale: f32) -> Vec2 {
+        let width = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let lines = text.lines().count().max(1);
+        glam::vec2(width as f32, lines as f32) * GLYPH_SIZE * scale
+    }
+    // The tex coords of a character in the font, anything it doesn't have comes out as a ?
+    pub fn glyph_tex_coords(c: char) -> (Vec2, Vec2) {
+        let code = if (FIRST_CHAR..=LAST_CHAR).contains(&(c as u32)) { c as u32 } else { '?' as u32 } - FIRST_CHAR;
+        let cell = glam::vec2((code % FONT_COLUMNS) as f32, (code / FONT_COLUMNS) as f32);
+        let cell_size = glam::vec2(1.0 / FONT_COLUMNS as f32, 1.0 / FONT_ROWS as f32);
+        (cell * cell_size, (cell + 1.0) * cell_size)
+    }
+    // Draws text with its top left at position, returns how much space it took up
+    pub fn text(&mut self, position: Vec2, text: &str, scale: f32, colour: [f32; 4]) -> Vec2 {
+        let glyph_size = GLYPH_SIZE * scale;
+        for (line_number, line) in text.lines().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                if c == ' ' { continue; }
+                let (tex_min, tex_max) = Ui::glyph_tex_coords(c);
+                let glyph_position = position + glam::vec2(column as f32, line_number as f32) * glyph_size;
+                self.quad(Rect::new(glyph_position, glyph_size), tex_min, tex_max, UI_TEXTURE_FONT, colour);
+            }
+        }
+        Ui::text_size(text, scale)
+    }
+}
+
+// -- Widgets -- //
+
+pub const CROSSHAIR_SIZE: f32 = 16.0;
+pub const CROSSHAIR_THICKNESS: f32 = 2.0;
+pub const HOTBAR_SLOT_SIZE: f32 = 44.0;
+pub const HOTBAR_ICON_INSET: f32 = 6.0;
+pub const HOTBAR_MARGIN: f32 = 8.0;
+pub const PANEL_PADDING: f32 = 6.0;
+
+// A plus in the middle of the screen
+pub fn crosshair(ui: &mut Ui) {
+    let center = ui.screen_size / 2.0;
+    ui.rect(Rect::centered(center, glam::vec2(CROSSHAIR_SIZE, CROSSHAIR_THICKNESS)), WHITE);
+    ui.rect(Rect::centered(center, glam::vec2(CROSSHAIR_THICKNESS, CROSSHAIR_SIZE)), WHITE);
+}
+
+// Where each hotbar slot goes, in a row along the bottom middle of the screen
+pub fn hotbar_layout(screen_size: Vec2) -> [Rect; HOTBAR_SIZE] {
+    let width = HOTBAR_SLOT_SIZE * HOTBAR_SIZE as f32;
+    let start = glam::vec2((screen_size.x - width) / 2.0, screen_size.y - HOTBAR_SLOT_SIZE - HOTBAR_MARGIN);
+    std::array::from_fn(|slot| Rect::new(start + glam::vec2(slot as f32 * HOTBAR_SLOT_SIZE, 0.0), Vec2::splat(HOTBAR_SLOT_SIZE)))
+}
+
+// The hotbar with an icon for each block, the selected slot outlined, and the selected block's name above it
+pub fn hotbar(ui: &mut Ui, hotbar: &Hotbar, voxel_data_manager: &VoxelDataManager, icons: &IconAtlas) {
+    let layout = hotbar_layout(ui.screen_size);
+    for (slot, rect) in layout.iter().enumerate() {
+        ui.rect(rect.inset(1.0), PANEL_COLOUR);
+        if let Some(voxel_id) = hotbar.slots[slot] {
+            ui.icon(rect.inset(HOTBAR_ICON_INSET), icons, voxel_id, WHITE);
+        }
+    }
+    ui.rect_outline(layout[hotbar.selected], 3.0, WHITE);
+    if let Some(voxel_id) = hotbar.selected_voxel() {
+        let name = voxel_data_manager.get_name(voxel_id);
+        let size = Ui::text_size(&name, 1.0);
+        let position = glam::vec2((ui.screen_size.x - size.x) / 2.0, layout[0].position.y - size.y - HOTBAR_MARGIN);
+        ui.text(position, &name, 1.0, WHITE);
+    }
+}
+
+// Lines of text on a see-through background, with its top left at position. Returns the space it took up
+pub fn text_panel(ui: &mut Ui, position: Vec2, lines: &[String]) -> Rect {
+    let text = lines.join("\n");
+    let rect = Rect::new(position, Ui::text_size(&text, 1.0) + PANEL_PADDING * 2.0);
+    ui.rect(rect, PANEL_COLOUR);
+    ui.text(position + PANEL_PADDING, &text, 1.0, WHITE);
+    rect
+}
+
+// Frames taking this long (in seconds) fill the frame time graph
+pub const FRAME_GRAPH_MAX: f32 = 1.0 / 20.0;
+pub const FRAME_GRAPH_SIZE: Vec2 = glam::vec2(240.0, 60.0);
+
+// A bar for each frame time, the newest on the right. Green is 60fps or better, yellow 30fps or better, red is worse
+pub fn frame_time_graph(ui: &mut Ui, rect: Rect, metrics: &Metrics) {
+    ui.rect(rect, PANEL_COLOUR);
+    let bar_width = rect.size.x / FRAME_HISTORY as f32;
+    let frame_times: Vec<f32> = metrics.frame_times().collect();
+    let start_x = rect.max().x - frame_times.len() as f32 * bar_width;
+    for (i, &frame_time) in frame_times.iter().enumerate() {
+        let height = (frame_time / FRAME_GRAPH_MAX).min(1.0) * rect.size.y;
+        let colour = if frame_time <= 1.0 / 60.0 { [0.3, 0.9, 0.3, 1.0] }
+            else if frame_time <= 1.0 / 30.0 { [0.9, 0.9, 0.3, 1.0] }
+            else { [0.9, 0.3, 0.3, 1.0] };
+        ui.rect(Rect::new(glam::vec2(start_x + i as f32 * bar_width, rect.max().y - height), glam::vec2(bar_width, height)), colour);
+    }
+    // A line where 60fps is
+    let target_y = rect.max().y - (1.0 / 60.0) / FRAME_GRAPH_MAX * rect.size.y;
+    ui.rect(Rect::new(glam::vec2(rect.position.x, target_y), glam::vec2(rect.size.x, 1.0)), WHITE);
+}
+
+// Everything the debug overlay shows that isn't in the metrics
+pub struct DebugInfo<'a> {
+    pub camera: &'a Camera,
+    // The voxel being looked at and its name
+    pub target: Option<(VoxelPosition, String)>,
+    pub loaded_chunks: usize,
+    pub meshed_chunks: usize,
+    pub drawn_chunks: usize,
+    pub vertices: usize,
+    pub indices: usize,
+}
+
+// Which way along an axis the camera's mostly looking
+fn facing_name(forward: glam::Vec3) -> &'static str {
+    if forward.x.abs() > forward.z.abs() {
+        if forward.x > 0.0 { "+X" } else { "-X" }
+    } else if forward.z > 0.0 { "+Z" } else { "-Z" }
+}
+
+fn milliseconds(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// The debug overlay's text, one string per line
+pub fn debug_lines(info: &DebugInfo, metrics: &Metrics) -> Vec<String> {
+    let position = info.camera.position;
+    let voxel = position.floor().as_ivec3();
+    let mut lines = vec![
+        format!("{:.0} fps ({:.1} ms avg, {:.1} ms max)", metrics.fps(), metrics.average_frame_time() * 1000.0, metrics.max_frame_time() * 1000.0),
+        format!("XYZ: {:.2} {:.2} {:.2}", position.x, position.y, position.z),
+        format!("Chunk: {} {} {}  Local: {} {} {}", Convert::global_to_chunk(voxel).x, Convert::global_to_chunk(voxel).y, Convert::global_to_chunk(voxel).z,
+            Convert::global_to_local(voxel).x, Convert::global_to_local(voxel).y, Convert::global_to_local(voxel).z),
+        format!("Facing: {} (yaw {:.1}, pitch {:.1})", facing_name(info.camera.forward()), info.camera.rotation.y.to_degrees(), info.camera.rotation.x.to_degrees()),
+        match &info.target {
+            Some((pos, name)) => format!("Looking at: {} ({} {} {})", name, pos.x, pos.y, pos.z),
+            None => "Looking at: Nothing".to_string(),
+        },
+        format!("Chunks: {} loaded, {} meshed, {} drawn", info.loaded_chunks, info.meshed_chunks, info.drawn_chunks),
+        format!("Vertices: {}  Indices: {}", info.vertices, info.indices),
+    ];
+    for (name, stats) in metrics.timings() {
+        lines.push(format!("{}: {:.2} ms last, {:.2} ms avg, {:.2} ms max ({})", name,
+            milliseconds(stats.last), milliseconds(stats.average()), milliseconds(stats.max), stats.count));
+    }
+    lines
+}
+
+// The debug text in the top left, with the frame time graph under it
+pub fn debug_overlay(ui: &mut Ui, position: Vec2, info: &DebugInfo, metrics: &Metrics) -> Rect {
+    let panel = text_panel(ui, position, &debug_lines(info, metrics));
+    let graph = Rect::new(glam::vec2(position.x, panel.max().y + PANEL_PADDING), FRAME_GRAPH_SIZE);
+    frame_time_graph(ui, graph, metrics);
+    Rect::new(position, glam::vec2(panel.size.x.max(graph.size.x), graph.max().y - position.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotbar_is_centred_along_the_bottom() {
+        let screen_size = glam::vec2(800.0, 600.0);
+        let layout = hotbar_layout(screen_size);
+        let (first, last) = (layout[0], layout[HOTBAR_SIZE - 1]);
+        assert_eq!(first.position.x, screen_size.x - last.max().x);
+        assert_eq!(last.max().x - first.position.x, HOTBAR_SLOT_SIZE * HOTBAR_SIZE as f32);
+        for rect in layout {
+            assert_eq!(rect.max().y, screen_size.y - HOTBAR_MARGIN);
+            assert_eq!(rect.size, Vec2::splat(HOTBAR_SLOT_SIZE));
+        }
+    }
+
+    #[test]
+    fn rects_contain_their_top_left_but_not_their_bottom_right() {
+        let rect = Rect::new(glam::vec2(10.0, 20.0), glam::vec2(30.0, 40.0));
+        assert!(rect.contains(glam::vec2(10.0, 20.0)));
+        assert!(rect.contains(glam::vec2(39.9, 59.9)));
+        assert!(!rect.contains(glam::vec2(40.0, 30.0)));
+        assert!(!rect.contains(glam::vec2(20.0, 60.0)));
+        assert!(!rect.contains(glam::vec2(9.9, 30.0)));
+    }
+
+    #[test]
+    fn insetting_shrinks_every_side() {
+        let rect = Rect::new(glam::vec2(10.0, 20.0), glam::vec2(30.0, 40.0));
+        assert_eq!(rect.inset(5.0), Rect::new(glam::vec2(15.0, 25.0), glam::vec2(20.0, 30.0)));
+        assert_eq!(rect.inset(-5.0), Rect::new(glam::vec2(5.0, 15.0), glam::vec2(40.0, 50.0)));
+        // Insetting past the middle leaves nothing rather than a negative size
+        assert_eq!(rect.inset(20.0).size, glam::vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn text_size_uses_the_widest_line() {
+        assert_eq!(Ui::text_size("ab\nabcd\nabc", 1.0), glam::vec2(4.0, 3.0) * GLYPH_SIZE);
+        assert_eq!(Ui::text_size("ab\nabcd", 2.0), glam::vec2(8.0, 4.0) * GLYPH_SIZE);
+        // Nothing at all still takes up a line
+        assert_eq!(Ui::text_size("", 1.0), glam::vec2(0.0, GLYPH_SIZE.y));
+    }
+
+    #[test]
+    fn missing_glyphs_come_out_as_question_marks() {
+        let question_mark = Ui::glyph_tex_coords('?');
+        assert_eq!(Ui::glyph_tex_coords('é'), question_mark);
+        assert_eq!(Ui::glyph_tex_coords('\t'), question_mark);
+        assert_ne!(Ui::glyph_tex_coords('A'), question_mark);
+        // 'A' is 33 characters in, so the second column of the third row
+        let (tex_min, tex_max) = Ui::glyph_tex_coords('A');
+        assert_eq!(tex_min, glam::vec2(1.0 / FONT_COLUMNS as f32, 2.0 / FONT_ROWS as f32));
+        assert_eq!(tex_max, glam::vec2(2.0 / FONT_COLUMNS as f32, 3.0 / FONT_ROWS as f32));
+    }
+
+    #[test]
+    fn text_gets_a_quad_for_every_character_but_spaces() {
+        let mut ui = Ui::new(glam::vec2(800.0, 600.0));
+        let size = ui.text(glam::vec2(10.0, 10.0), "a b\n c", 1.0, WHITE);
+        assert_eq!(size, glam::vec2(3.0, 2.0) * GLYPH_SIZE);
+        assert_eq!((ui.vertices.len(), ui.indices.len()), (3 * 4, 3 * 6));
+        assert!(ui.vertices.iter().all(|v| v.layer == UI_TEXTURE_FONT));
+        // The c on the second line goes one glyph across and one down
+        assert_eq!(ui.vertices[8].position, (glam::vec2(10.0, 10.0) + GLYPH_SIZE).to_array());
+        assert_eq!(*ui.indices.last().unwrap(), 8);
+    }
+}
@@ -0,0 +1,22 @@
+#version 140
+
+in vec2 position;
+in vec2 tex_coords;
+in vec4 colour;
+in int layer;
+
+out vec2 v_tex_coords;
+out vec4 v_colour;
+flat out int v_layer;
+
+uniform vec2 screen_size;
+
+void main() {
+    // Textures are uploaded upside down, so flip them back
+    v_tex_coords = vec2(tex_coords.x, 1.0 - tex_coords.y);
+    v_colour = colour;
+    v_layer = layer;
+    // Pixels from the top left to clip space
+    vec2 clip = position / screen_size * 2.0 - 1.0;
+    gl_Position = vec4(clip.x, -clip.y, 0.0, 1.0);
+}
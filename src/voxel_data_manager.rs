@@ -1,16 +1,61 @@
 use std::collections::HashMap;
 
-use crate::chunk::VoxelID;
+use crate::{chunk::VoxelID, fluid::Fluid, tick_scheduler::TickHandler, block_updates::NeighbourUpdateHandler, physics::CollisionShape, textures::{self, TextureAtlas}};
+
+// How a voxel is shaped
+pub const VOXEL_TYPE_CUBE: u8 = 0;
+pub const VOXEL_TYPE_CROSS: u8 = 1;
+pub const VOXEL_TYPE_FLUID: u8 = 2;
+
+// Which mesh a voxel's faces go into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderLayer {
+    Opaque,      // Fully solid, hides anything behind it
+    Cutout,      // Pixels are either solid or see-through (e.g. leaves)
+    Translucent, // Partly see-through, has to be drawn after everything else and sorted (e.g. glass)
+}
 
 pub struct VoxelData {
     pub name: String,
     pub texture_ids: [u32; 6], // left top front right bottom back
     pub voxel_type: u8,
+    pub render_layer: RenderLayer,
+    pub fluid: Option<Fluid>,
+    pub on_scheduled_tick: Option<TickHandler>,
+    pub on_random_tick: Option<TickHandler>,
+    pub on_neighbour_update: Option<NeighbourUpdateHandler>,
+    // How many stages a crop grows through (stored in its state), 0 if it doesn't grow
+    pub growth_stages: u8,
+    // How much an explosion's ray gets weakened by going through this voxel
+    pub blast_resistance: f32,
+    // How strong the explosion is when this voxel is set off, 0 if it isn't an explosive
+    pub explosive_power: f32,
+    // Whether the voxel falls when there's nothing under it
+    pub gravity: bool,
+    pub collision_shape: CollisionShape,
+    // How quickly things speed up and slow down when walking on it, 1 is normal and lower is more slippery
+    pub friction: f32,
 }
 
 impl VoxelData {
     pub fn new(name: String, texture_ids: [u32; 6], voxel_type: u8) -> Self {
-        VoxelData { name: name, texture_ids: texture_ids, voxel_type: voxel_type }
+        // Cubes are opaque unless told otherwise, cross shaped voxels always have gaps in them, and fluids are see-through
+        let render_layer = match voxel_type {
+            VOXEL_TYPE_CUBE => RenderLayer::Opaque,
+            VOXEL_TYPE_FLUID => RenderLayer::Translucent,
+            _ => RenderLayer::Cutout,
+        };
+        // Plants get blown away by anything, and fluids soak explosions up
+        let blast_resistance = match voxel_type {
+            VOXEL_TYPE_CUBE => 3.0,
+            VOXEL_TYPE_FLUID => 100.0,
+            _ => 0.0,
+        };
+        // Only cubes get in the way by default, you can walk through plants and swim through fluids
+        let collision_shape = if voxel_type == VOXEL_TYPE_CUBE { CollisionShape::Full } else { CollisionShape::None };
+        VoxelData { name: name, texture_ids: texture_ids, voxel_type: voxel_type, render_layer, fluid: None,
+            on_scheduled_tick: None, on_random_tick: None, on_neighbour_update: None, growth_stages: 0,
+            blast_resistance, explosive_power: 0.0, gravity: false, collision_shape, friction: 1.0 }
     }
 }
 
@@ -19,7 +64,9 @@ fn find_in_vec(v: &Vec<&str>, f: &str) -> u32 {
 }
 
 pub struct VoxelDataManager {
-    voxel_data: Vec<VoxelData>
+    voxel_data: Vec<VoxelData>,
+    // The voxel textures by texture ID, the right way up, kept for anything that draws them on the CPU (e.g. icons)
+    textures: Vec<image::RgbaImage>,
 }
 impl VoxelDataManager {
     pub fn new(in_data: Vec<(&str, u8, Vec<&str>)>, images: &mut Vec<glium::texture::RawImage2d<'_, u8>>) -> Self {
@@ -52,25 +99,72 @@ impl VoxelDataManager {
                     ],
                 _ => [0; 6],
             };
-            voxel_data.push(VoxelData { name: voxel_name.to_string(), texture_ids: texture_ids, voxel_type: voxel_type });
+            voxel_data.push(VoxelData::new(voxel_name.to_string(), texture_ids, voxel_type));
         }
         // Load images
+        let mut textures = vec![];
         for image_name in &image_names {
-            use image::GenericImageView;
-            let loaded_image = image::open("res/textures/".to_owned() + &image_name.to_string() + ".png").unwrap();
+            let loaded_image = image::open("res/textures/".to_owned() + &image_name.to_string() + ".png").unwrap().to_rgba8();
             let image = glium::texture::RawImage2d::from_raw_rgba_reversed(
-            &loaded_image.to_rgba8().into_raw(),
+            loaded_image.as_raw(),
             loaded_image.dimensions());
             images.push(image);
+            textures.push(loaded_image);
         }
 
-        Self { voxel_data: voxel_data }
+        Self { voxel_data: voxel_data, textures }
+    }
+    // Every voxel in the game, world generation places some of them by ID so new ones go on the end
+    pub fn with_default_voxels(images: &mut Vec<glium::texture::RawImage2d<'_, u8>>) -> Self {
+        let mut voxel_data_manager = VoxelDataManager::new(vec![
+            ("Air",         1, vec!["missing"]),
+            ("Grass Block", 0, vec!["grass_top", "dirt", "grass_side"]),
+            ("Dirt",        0, vec!["dirt"]),
+            ("Stone",       0, vec!["stone"]),
+            ("Deep Stone",  0, vec!["deep_stone"]),
+            ("Sand",        0, vec!["sand"]),
+            ("Oak Log",     0, vec!["oak_log_top", "oak_log_top", "oak_log_side"]),
+            ("Oak Planks",  0, vec!["oak_planks"]),
+            ("Leaves",      0, vec!["leaves"]),
+            ("Grass",       1, vec!["grass"]),
+            ("Cobblestone", 0, vec!["cobblestone"]),
+            ("Bricks",      0, vec!["bricks"]),
+            ("C4",          0, vec!["c4", "c4", "c4_side"]),
+            ("Glass",       0, vec!["glass"]),
+            ("Water",       2, vec!["water"]),
+            ("Lava",        2, vec!["lava"]),
+            ("Wheat",       1, vec!["wheat"]),
+        ], images);
+        for (name, render_layer) in [("Leaves", RenderLayer::Cutout), ("Glass", RenderLayer::Translucent), ("Lava", RenderLayer::Opaque)] {
+            voxel_data_manager.configure(name, |data| data.render_layer = render_layer);
+        }
+        voxel_data_manager
     }
     
     pub fn get_texture_id(&self, voxel: VoxelID, side: usize) -> u32 {
         self.voxel_data[voxel as usize].texture_ids[side]
     }
 
+    pub fn get_texture(&self, texture_id: u32) -> &image::RgbaImage {
+        &self.textures[texture_id as usize]
+    }
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+
+    // Every mip level of every texture, by texture ID. Textures used by cutout voxels keep their alpha coverage
+    // at each level, so they don't fade away in the distance
+    pub fn build_mipmaps(&self) -> Vec<Vec<image::RgbaImage>> {
+        self.textures.iter().enumerate().map(|(texture_id, texture)| {
+            let is_cutout = self.voxel_data.iter().any(|v| v.render_layer == RenderLayer::Cutout && v.texture_ids.contains(&(texture_id as u32)));
+            textures::build_mip_chain(texture, is_cutout.then_some(textures::ALPHA_CUTOFF))
+        }).collect()
+    }
+    // The textures packed into a 2D atlas with mipmaps, for backends without texture arrays
+    pub fn build_texture_atlas(&self, padding: u32) -> TextureAtlas {
+        TextureAtlas::pack(&self.build_mipmaps(), padding)
+    }
+
     pub fn get_name(&self, voxel: VoxelID) -> String {
         self.voxel_data[voxel as usize].name.clone()
     }
@@ -78,4 +172,40 @@ impl VoxelDataManager {
     pub fn get_voxel_type(&self, voxel: VoxelID) -> u8 {
         self.voxel_data[voxel as usize].voxel_type
     }
+
+    pub fn get_render_layer(&self, voxel: VoxelID) -> RenderLayer {
+        self.voxel_data[voxel as usize].render_layer
+    }
+
+    // Whether a voxel completely hides whatever's behind it
+    pub fn is_opaque_cube(&self, voxel: VoxelID) -> bool {
+        self.get_voxel_type(voxel) == VOXEL_TYPE_CUBE && self.get_render_layer(voxel) == RenderLayer::Opaque
+    }
+
+    // Finds the ID of a voxel from its name
+    pub fn get_id(&self, name: &str) -> Option<VoxelID> {
+        self.voxel_data.iter().position(|v| v.name == name).map(|i| i as VoxelID)
+    }
+
+    pub fn get_voxel_data(&self, voxel: VoxelID) -> &VoxelData {
+        &self.voxel_data[voxel as usize]
+    }
+    // For changing a voxel's properties after the manager's been made
+    pub fn get_voxel_data_mut(&mut self, voxel: VoxelID) -> &mut VoxelData {
+        &mut self.voxel_data[voxel as usize]
+    }
+    // Changes the properties of a voxel by name, does nothing if there isn't one with that name
+    pub fn configure(&mut self, name: &str, f: impl FnOnce(&mut VoxelData)) {
+        if let Some(id) = self.get_id(name) {
+            f(self.get_voxel_data_mut(id));
+        }
+    }
+
+    // How many voxels there are, including air
+    pub fn len(&self) -> usize {
+        self.voxel_data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.voxel_data.is_empty()
+    }
 }
\ No newline at end of file
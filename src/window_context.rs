@@ -15,19 +15,24 @@ impl Keyboard {
         Self { keys_held: vec![], keys_pressed: vec![], keys_released: vec![] }
     }
     pub fn process_input(&mut self, input: glium::glutin::event::KeyboardInput) {
-        if input.virtual_keycode.is_none() { // Eh? Dunno if this should ever happen but it must be an option for a reason..
+        let Some(key) = input.virtual_keycode else { // Eh? Dunno if this should ever happen but it must be an option for a reason..
             return;
-        }
-        match input.state {
+        };
+        self.process_key(key, input.state);
+    }
+    // Same as process_input but without the rest of the event, for when input's replayed from a recording
+    pub fn process_key(&mut self, key: VirtualKeyCode, state: ElementState) {
+        let key = Some(key);
+        match state {
             ElementState::Pressed => {
-                if !self.keys_held.contains(&input.virtual_keycode) {
-                    self.keys_held.push(input.virtual_keycode);
-                    self.keys_pressed.push(input.virtual_keycode);
+                if !self.keys_held.contains(&key) {
+                    self.keys_held.push(key);
+                    self.keys_pressed.push(key);
                 }
             },
             ElementState::Released => {
-                self.keys_held.retain(|&x| x != input.virtual_keycode);
-                self.keys_released.push(input.virtual_keycode);
+                self.keys_held.retain(|&x| x != key);
+                self.keys_released.push(key);
             },
         }
     }
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use crate::{chunk::{Convert, Chunk, ChunkPosition, VoxelPosition, VoxelID, CHUNK_SIZE, CHUNK_SIZE_MIN1}, chunk_manager::ChunkManager};
+
+pub type BlockHistogram = HashMap<VoxelID, u64>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceStatistics {
+    pub min_height: i32,
+    pub max_height: i32,
+    pub mean_height: f32,
+    // How many columns had a surface, columns with no loaded non-air voxels are skipped
+    pub column_count: u32,
+}
+
+// Queries for counting and finding voxels in the loaded world.
+// Anything that can be answered with the per-chunk voxel counts is, so whole chunks are never looped over unless they have to be
+impl ChunkManager {
+    // Counts every voxel in every loaded chunk
+    pub fn get_world_histogram(&self) -> BlockHistogram {
+        let mut histogram = BlockHistogram::new();
+        for chunk in self.chunks.values() {
+            add_counts_to_histogram(&mut histogram, chunk);
+        }
+        histogram
+    }
+
+    // Counts every loaded voxel between min and max (inclusive)
+    pub fn get_block_histogram(&self, min: VoxelPosition, max: VoxelPosition) -> BlockHistogram {
+        let (min, max) = (min.min(max), min.max(max));
+        let mut histogram = BlockHistogram::new();
+        for chunk in self.chunks_in_region(min, max) {
+            let (local_min, local_max) = local_bounds(chunk.position, min, max);
+            // If the whole chunk is inside the region, use the cached counts
+            if local_min == VoxelPosition::ZERO && local_max == VoxelPosition::splat(CHUNK_SIZE_MIN1) {
+                add_counts_to_histogram(&mut histogram, chunk);
+                continue;
+            }
+            for y in local_min.y..=local_max.y {
+                for z in local_min.z..=local_max.z {
+                    for x in local_min.x..=local_max.x {
+                        *histogram.entry(chunk.get_voxel_from_coordinate(glam::ivec3(x, y, z))).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        histogram
+    }
+
+    // Returns the global position of every voxel_id between min and max (inclusive)
+    pub fn find_voxels(&self, voxel_id: VoxelID, min: VoxelPosition, max: VoxelPosition) -> Vec<VoxelPosition> {
+        let (min, max) = (min.min(max), min.max(max));
+        let mut found = vec![];
+        for chunk in self.chunks_in_region(min, max) {
+            // No point looking through a chunk that doesn't have any
+            if chunk.get_voxel_count(voxel_id) == 0 { continue; }
+            let (local_min, local_max) = local_bounds(chunk.position, min, max);
+            for y in local_min.y..=local_max.y {
+                for z in local_min.z..=local_max.z {
+                    for x in local_min.x..=local_max.x {
+                        let local = glam::ivec3(x, y, z);
+                        if chunk.get_voxel_from_coordinate(local) == voxel_id {
+                            found.push(Convert::local_to_global(chunk.position, local));
+                        }
+                    }
+                }
+            }
+        }
+        // HashMap order is random, so sort to make the result stable
+        found.sort_by_key(|p| (p.y, p.z, p.x));
+        found
+    }
+
+    // Finds the height of the highest loaded non-air voxel in a column
+    pub fn get_surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        let chunk_position = Convert::global_to_chunk(glam::ivec3(x, 0, z));
+        let mut chunk_ys: Vec<i32> = self.chunks.keys().filter(|p| p.x == chunk_position.x && p.z == chunk_position.z).map(|p| p.y).collect();
+        chunk_ys.sort_unstable_by(|a, b| b.cmp(a));
+        self.surface_height_in_column(x, z, &chunk_ys)
+    }
+
+    // Works out the min, max and mean surface height of every column between min and max (inclusive)
+    pub fn get_surface_height_statistics(&self, min: glam::IVec2, max: glam::IVec2) -> Option<SurfaceStatistics> {
+        let (min, max) = (min.min(max), min.max(max));
+        // Which chunks are loaded in each chunk column, highest first
+        let mut chunk_columns: HashMap<(i32, i32), Vec<i32>> = HashMap::new();
+        for position in self.chunks.keys() {
+            chunk_columns.entry((position.x, position.z)).or_default().push(position.y);
+        }
+        for chunk_ys in chunk_columns.values_mut() {
+            chunk_ys.sort_unstable_by(|a, b| b.cmp(a));
+        }
+
+        let (mut min_height, mut max_height, mut total, mut column_count) = (i32::MAX, i32::MIN, 0i64, 0u32);
+        for x in min.x..=max.x {
+            for z in min.y..=max.y {
+                let chunk_position = Convert::global_to_chunk(glam::ivec3(x, 0, z));
+                let Some(chunk_ys) = chunk_columns.get(&(chunk_position.x, chunk_position.z)) else { continue; };
+                if let Some(height) = self.surface_height_in_column(x, z, chunk_ys) {
+                    min_height = min_height.min(height);
+                    max_height = max_height.max(height);
+                    total += height as i64;
+                    column_count += 1;
+                }
+            }
+        }
+        if column_count == 0 {
+            return None;
+        }
+        Some(SurfaceStatistics { min_height, max_height, mean_height: (total as f64 / column_count as f64) as f32, column_count })
+    }
+
+    // Returns how many (solid, air) voxels are in a chunk
+    pub fn get_chunk_solid_air_counts(&self, position: ChunkPosition) -> Option<(u32, u32)> {
+        self.get_chunk(position).map(|chunk| (chunk.solid_count(), chunk.air_count()))
+    }
+
+    // chunk_ys must be sorted highest first
+    fn surface_height_in_column(&self, x: i32, z: i32, chunk_ys: &[i32]) -> Option<i32> {
+        let local = Convert::global_to_local(glam::ivec3(x, 0, z));
+        let chunk_position = Convert::global_to_chunk(glam::ivec3(x, 0, z));
+        for &chunk_y in chunk_ys {
+            let chunk = match self.get_chunk(glam::ivec3(chunk_position.x, chunk_y, chunk_position.z)) {
+                Some(chunk) => chunk,
+                None => continue,
+            };
+            // Skip chunks that are all air
+            if chunk.solid_count() == 0 { continue; }
+            for y in (0..CHUNK_SIZE).rev() {
+                if chunk.get_voxel_from_coordinate(glam::ivec3(local.x, y, local.z)) != 0 {
+                    return Some(chunk_y * CHUNK_SIZE + y);
+                }
+            }
+        }
+        None
+    }
+
+    // Every loaded chunk that overlaps the region between min and max (inclusive)
+    fn chunks_in_region(&self, min: VoxelPosition, max: VoxelPosition) -> Vec<&Chunk> {
+        let (chunk_min, chunk_max) = (Convert::global_to_chunk(min), Convert::global_to_chunk(max));
+        let size = (chunk_max - chunk_min + 1).as_i64vec3();
+        // Small regions look up each chunk, big ones go through every loaded chunk instead
+        if size.x * size.y * size.z < self.chunks.len() as i64 {
+            let mut chunks = vec![];
+            for y in chunk_min.y..=chunk_max.y {
+                for z in chunk_min.z..=chunk_max.z {
+                    for x in chunk_min.x..=chunk_max.x {
+                        if let Some(chunk) = self.get_chunk(glam::ivec3(x, y, z)) {
+                            chunks.push(chunk);
+                        }
+                    }
+                }
+            }
+            chunks
+        } else {
+            self.chunks.iter()
+                .filter(|(p, _)| p.cmpge(chunk_min).all() && p.cmple(chunk_max).all())
+                .map(|(_, chunk)| chunk)
+                .collect()
+        }
+    }
+}
+
+fn add_counts_to_histogram(histogram: &mut BlockHistogram, chunk: &Chunk) {
+    for (voxel_id, &count) in chunk.get_voxel_counts().iter().enumerate() {
+        if count != 0 {
+            *histogram.entry(voxel_id as VoxelID).or_insert(0) += count as u64;
+        }
+    }
+}
+
+// The part of the region between min and max (inclusive) that's inside a chunk, in local coordinates
+fn local_bounds(chunk_position: ChunkPosition, min: VoxelPosition, max: VoxelPosition) -> (VoxelPosition, VoxelPosition) {
+    let origin = Convert::local_to_global(chunk_position, VoxelPosition::ZERO);
+    ((min - origin).max(VoxelPosition::ZERO), (max - origin).min(VoxelPosition::splat(CHUNK_SIZE_MIN1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::DEFAULT_VOXELS, voxel_data_manager::VoxelDataManager};
+
+    // Hills of stripes (IDs 1 to 3) with air above them, the same whichever chunk a voxel's in
+    fn test_voxel(pos: VoxelPosition) -> VoxelID {
+        let height = (pos.x * 3 + pos.z * 5).rem_euclid(40) - 10;
+        if pos.y > height { 0 } else { 1 + (pos.x + pos.y + pos.z).rem_euclid(3) as VoxelID }
+    }
+
+    // Two chunks wide and tall, with one corner chunk missing
+    fn test_world() -> ChunkManager {
+        let mut chunk_manager = ChunkManager::new(VoxelDataManager::with_default_voxels(&mut vec![]));
+        for position in [glam::ivec3(0, 0, 0), glam::ivec3(1, 0, 0), glam::ivec3(0, -1, 0)] {
+            let mut voxels = DEFAULT_VOXELS;
+            for (index, voxel) in voxels.iter_mut().enumerate() {
+                *voxel = test_voxel(Convert::local_to_global(position, Chunk::index_to_coordinates(index)));
+            }
+            chunk_manager.set_chunk(position, voxels);
+        }
+        chunk_manager
+    }
+
+    // Every loaded voxel in a region, one at a time
+    fn brute_force(chunk_manager: &ChunkManager, min: VoxelPosition, max: VoxelPosition) -> Vec<(VoxelPosition, VoxelID)> {
+        let mut voxels = vec![];
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    let pos = glam::ivec3(x, y, z);
+                    if let Some(voxel_id) = chunk_manager.get_voxel(pos) {
+                        voxels.push((pos, voxel_id));
+                    }
+                }
+            }
+        }
+        voxels
+    }
+
+    // Whole chunks, chunks cut off on one side, and regions that start or end right on a chunk border
+    const REGIONS: [(VoxelPosition, VoxelPosition); 4] = [
+        (glam::IVec3::new(0, -32, 0), glam::IVec3::new(36, 31, 31)),
+        (glam::IVec3::new(3, -7, 5), glam::IVec3::new(40, 20, 30)),
+        (glam::IVec3::new(31, 0, 0), glam::IVec3::new(32, 0, 31)),
+        (glam::IVec3::new(-4, -33, -2), glam::IVec3::new(0, -1, 0)),
+    ];
+
+    #[test]
+    fn block_histogram_matches_counting_by_hand() {
+        let chunk_manager = test_world();
+        for (min, max) in REGIONS {
+            let mut expected = BlockHistogram::new();
+            for (_, voxel_id) in brute_force(&chunk_manager, min, max) {
+                *expected.entry(voxel_id).or_insert(0) += 1;
+            }
+            assert_eq!(chunk_manager.get_block_histogram(min, max), expected, "{} to {}", min, max);
+            // The corners can be given either way round
+            assert_eq!(chunk_manager.get_block_histogram(max, min), expected);
+        }
+        let mut world = BlockHistogram::new();
+        for (_, voxel_id) in brute_force(&chunk_manager, glam::ivec3(-32, -64, -32), glam::ivec3(95, 63, 63)) {
+            *world.entry(voxel_id).or_insert(0) += 1;
+        }
+        assert_eq!(chunk_manager.get_world_histogram(), world);
+    }
+
+    #[test]
+    fn find_voxels_matches_searching_by_hand() {
+        let chunk_manager = test_world();
+        for (min, max) in REGIONS {
+            for voxel_id in 0..4 {
+                let mut expected: Vec<VoxelPosition> = brute_force(&chunk_manager, min, max).into_iter()
+                    .filter(|&(_, v)| v == voxel_id).map(|(pos, _)| pos).collect();
+                expected.sort_by_key(|p| (p.y, p.z, p.x));
+                assert_eq!(chunk_manager.find_voxels(voxel_id, min, max), expected, "{} from {} to {}", voxel_id, min, max);
+            }
+        }
+        // Nothing's looked for in chunks that don't have any
+        assert!(chunk_manager.find_voxels(200, glam::ivec3(0, 0, 0), glam::ivec3(63, 31, 31)).is_empty());
+    }
+
+    #[test]
+    fn surface_heights_match_scanning_down() {
+        let chunk_manager = test_world();
+        let scan = |x: i32, z: i32| (-64..64).rev().find(|&y| chunk_manager.get_voxel(glam::ivec3(x, y, z)).is_some_and(|v| v != 0));
+        // Either side of the chunk borders, and columns with nothing loaded
+        for (x, z) in [(0, 0), (31, 0), (32, 0), (63, 31), (5, 17), (-1, 0), (0, 32), (64, 0)] {
+            assert_eq!(chunk_manager.get_surface_height(x, z), scan(x, z), "column {} {}", x, z);
+        }
+
+        let (min, max) = (glam::ivec2(-3, 20), glam::ivec2(40, 34));
+        let heights: Vec<i32> = (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |z| (x, z))).filter_map(|(x, z)| scan(x, z)).collect();
+        let statistics = chunk_manager.get_surface_height_statistics(min, max).unwrap();
+        assert_eq!(statistics.column_count as usize, heights.len());
+        assert_eq!(statistics.min_height, *heights.iter().min().unwrap());
+        assert_eq!(statistics.max_height, *heights.iter().max().unwrap());
+        let mean = heights.iter().sum::<i32>() as f32 / heights.len() as f32;
+        assert!((statistics.mean_height - mean).abs() < 1e-4);
+        assert_eq!(chunk_manager.get_surface_height_statistics(glam::ivec2(-10, -10), glam::ivec2(-1, -1)), None);
+    }
+
+    #[test]
+    fn solid_and_air_counts() {
+        let chunk_manager = test_world();
+        let voxels = brute_force(&chunk_manager, glam::ivec3(32, 0, 0), glam::ivec3(63, 31, 31));
+        let air = voxels.iter().filter(|&&(_, v)| v == 0).count() as u32;
+        assert_eq!(chunk_manager.get_chunk_solid_air_counts(glam::ivec3(1, 0, 0)), Some((voxels.len() as u32 - air, air)));
+        assert_eq!(chunk_manager.get_chunk_solid_air_counts(glam::ivec3(1, -1, 0)), None);
+    }
+}